@@ -5,6 +5,8 @@ use tokio::sync::Mutex;
 
 use specta::Type;
 
+use crate::error::CommandResult;
+
 // <------------State------------>
 #[derive(Clone, Serialize, Deserialize, Debug, Type)]
 pub struct AppState {
@@ -20,13 +22,13 @@ impl Default for AppState {
 // <------------Commands------------>
 #[tauri::command]
 #[specta::specta]
-pub async fn get_state(state: tauri::State<'_, ManagedState>) -> Result<AppState, String> {
+pub async fn get_state(state: tauri::State<'_, ManagedState>) -> CommandResult<AppState> {
     Ok(state.get().await)
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn increment_click(state: tauri::State<'_, ManagedState>) -> Result<(), String> {
+pub async fn increment_click(state: tauri::State<'_, ManagedState>) -> CommandResult<()> {
     state
         .update(|s| {
             s.clicks += 1;
@@ -38,7 +40,7 @@ pub async fn increment_click(state: tauri::State<'_, ManagedState>) -> Result<()
 
 #[tauri::command]
 #[specta::specta]
-pub async fn reset_clicks(state: tauri::State<'_, ManagedState>) -> Result<(), String> {
+pub async fn reset_clicks(state: tauri::State<'_, ManagedState>) -> CommandResult<()> {
     state
         .update(|s| {
             s.clicks = 0;
@@ -1,37 +1,131 @@
 use std::{
-    collections::VecDeque,
-    sync::{Arc, OnceLock},
+    collections::{HashSet, VecDeque},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, OnceLock,
+    },
     time::Duration,
 };
 
 use crate::{
+    bookmarks,
+    error::{CommandError, CommandResult},
     library_service::library_service,
+    media_lib_cmd,
     models::{FullTrack, Track},
-    traits::Shuffle,
+    play_tracker::{self, Listen},
+    radio, scrobbler,
+    session::{self, Session},
+    settings::ManagedSettings,
+    sleep_timer::{self, SleepTimer},
+    traits::{BalancedShuffle, Shuffle},
 };
 use libaurex::{aurex::Player, enums::EngineSignal};
 use serde::{Deserialize, Serialize};
 use specta::Type;
 use tauri::{AppHandle, Emitter, Manager};
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, Notify};
 
 fn player_callback(event: EngineSignal, app_handle: AppHandle) {
     tokio::spawn(async move {
         let state = app_handle.state::<ManagedPlayer>();
         if event == EngineSignal::MediaEnd {
             println!("Media ended");
+            track_ended(state).await;
+        }
+    });
+}
 
-            let player = state.get().await;
+async fn track_ended(state: tauri::State<'_, ManagedPlayer>) {
+    // Stopping here, so the next track isn't started only to be paused again
+    if sleep_timer::track_finished(&state).await {
+        state.next_segment.lock().await.take();
 
-            if player.queue.is_empty() {
-                drop(player);
-                _ = clear(state).await;
-                return;
+        let finished = state.player.lock().await.currently_playing.clone();
+        if let Some(finished) = &finished {
+            bookmarks::forget_position(finished);
+        }
+
+        sleep_timer::fire(&state).await;
+        return;
+    }
+
+    on_media_end(state).await;
+}
+
+/// The engine doesn't know where tracks from a CUE sheet end, so the progress
+/// publisher calls this once it passes the end of one. When the next track
+/// picks up right where this one stops, the engine keeps playing through.
+async fn segment_ended(state: tauri::State<'_, ManagedPlayer>) {
+    let continuation = {
+        let player = state.player.lock().await;
+        match (&player.currently_playing, player.upcoming()) {
+            (Some(current), Some(upcoming)) if is_next_segment(current, upcoming) => {
+                Some(upcoming.clone())
             }
+            _ => None,
+        }
+    };
 
-            _ = next(state.clone()).await;
+    match continuation {
+        Some(upcoming) => *state.next_segment.lock().await = Some(upcoming),
+        None => {
+            let audio_engine = audio_player().lock().await;
+            _ = audio_engine.pause().await;
         }
-    });
+    }
+
+    track_ended(state).await;
+}
+
+fn is_next_segment(current: &FullTrack, next: &FullTrack) -> bool {
+    current.track.id != next.track.id
+        && current.track.audio_path() == next.track.audio_path()
+        && current.track.end_offset == next.track.start_offset
+}
+
+async fn on_media_end(state: tauri::State<'_, ManagedPlayer>) {
+    let next_segment = state.next_segment.lock().await.take();
+    let player = state.get().await;
+
+    if let Some(finished) = &player.currently_playing {
+        bookmarks::forget_position(finished);
+    }
+
+    // The engine has already played on into the next CUE segment, unless
+    // the queue changed underneath it
+    let continued = match (next_segment, player.upcoming()) {
+        (Some(p), Some(u)) => p.track.id == u.track.id,
+        _ => false,
+    };
+
+    if player.looping == LoopType::LoopOnce {
+        if let Some(track) = player.currently_playing.clone() {
+            if !continued {
+                match load(state.clone(), track.clone()).await {
+                    Ok(_) => _ = play(state.clone()).await,
+                    Err(_) => _ = stop_on(&state, track).await,
+                }
+            }
+            return;
+        }
+    }
+
+    if player.queue.is_empty() && player.looping != LoopType::LoopOver && !player.autoplay {
+        drop(player);
+        _ = clear(state).await;
+        return;
+    }
+
+    drop(player);
+
+    // Autoplay can come up empty handed, and when nothing would load the
+    // player has already stopped on the queue as it is
+    match advance(state.clone(), continued).await {
+        Ok(true) => {}
+        Ok(false) => _ = clear(state).await,
+        Err(e) => eprintln!("Failed to start the next track: {}", e),
+    }
 }
 
 static INSTANCE: OnceLock<Mutex<Arc<Player>>> = OnceLock::new();
@@ -54,20 +148,155 @@ pub fn audio_player() -> &'static Mutex<Arc<Player>> {
     INSTANCE.get().expect("Audio engine not initialized")
 }
 
+/// Puts the engine back where the saved session left off: the current track
+/// is loaded paused at the stored position.
+pub async fn restore_session(app_handle: AppHandle) -> CommandResult<()> {
+    let state = app_handle.state::<ManagedPlayer>();
+    let player = state.get().await;
+
+    if let Some(track) = player.currently_playing {
+        {
+            let audio_engine = audio_player().lock().await;
+            audio_engine
+                .clone()
+                .load(track.track.audio_path())
+                .await
+                .map_err(CommandError::engine)?;
+
+            let offset = track.track.start() + player.position;
+            if offset > 0.0 {
+                audio_engine
+                    .seek(offset)
+                    .await
+                    .map_err(CommandError::engine)?;
+            }
+        }
+
+        play_tracker::start(&state, &track, player.position).await;
+    }
+
+    Ok(())
+}
+
+/// The engine's position within the current track. For tracks from a CUE
+/// sheet that isn't the position within the file.
+pub async fn current_progress(state: &ManagedPlayer) -> Option<f64> {
+    let start = state
+        .player
+        .lock()
+        .await
+        .currently_playing
+        .as_ref()
+        .map_or(0.0, |t| t.track.start());
+
+    let audio_engine = audio_player().lock().await;
+    let progress = audio_engine.get_progress().await.ok()?;
+    Some((progress - start).max(0.0))
+}
+
+/// Stores the session together with the engine's current position.
+pub async fn save_session(app_handle: AppHandle) {
+    let state = app_handle.state::<ManagedPlayer>();
+
+    let progress = current_progress(&state).await;
+
+    let current = state.player.lock().await.currently_playing.clone();
+    if let (Some(track), Some(position)) = (current, progress) {
+        bookmarks::remember_position(&state, &track, position).await;
+    }
+
+    let mut player = state.player.lock().await;
+    if let Some(position) = progress {
+        player.position = position;
+    }
+
+    session::save_now(&player.to_session());
+}
+
+/// Publishes `progress-changed` at the rate from the settings while something
+/// is playing, and sleeps until playback resumes otherwise.
 pub fn track_progress(app_handle: AppHandle) {
     tauri::async_runtime::spawn(async move {
+        let state = app_handle.state::<ManagedPlayer>();
+
         loop {
-            let audio_engine = audio_player().lock().await;
+            // Registered before checking so a resume in between isn't missed
+            let resumed = state.playing.notified();
+
+            if state.player.lock().await.state != PlayerState::Playing {
+                resumed.await;
+                continue;
+            }
+
+            let interval = app_handle
+                .state::<ManagedSettings>()
+                .get()
+                .await
+                .progress_interval;
+
+            // The engine plays on into the next CUE segment, so the end of
+            // one is polled closely to stop it on time
+            let remaining = segment_remaining(&*state.player.lock().await);
+            let wait = remaining.map_or(interval.into(), |left| {
+                u64::from(interval).min(left.max(SEGMENT_END_POLL))
+            });
+            tokio::time::sleep(Duration::from_millis(wait)).await;
+
+            let progress = {
+                let audio_engine = audio_player().lock().await;
+                audio_engine.get_progress().await
+            };
+
+            let progress = match progress {
+                Ok(progress) => progress,
+                Err(_) => {
+                    eprintln!("Failed to read playback progress");
+                    continue;
+                }
+            };
 
-            let progress = audio_engine.get_progress().await.unwrap();
+            let (progress, ended, ab_loop) = {
+                let mut player = state.player.lock().await;
+                let Some(current) = &player.currently_playing else {
+                    continue;
+                };
+
+                let ended = current.track.end_offset.is_some_and(|end| progress >= end);
+                let progress = (progress - current.track.start()).max(0.0);
+
+                player.position = progress;
+                (progress, ended, player.ab_loop)
+            };
+
+            if ended {
+                segment_ended(state.clone()).await;
+                continue;
+            }
+
+            if let Some(ab_loop) = ab_loop {
+                if progress >= ab_loop.end {
+                    _ = seek(state.clone(), ab_loop.start).await;
+                    continue;
+                }
+            }
 
             _ = app_handle.emit("progress-changed", progress);
 
-            _ = tokio::time::sleep(Duration::from_millis(20)).await;
+            play_tracker::progress(&state, progress).await;
         }
     });
 }
 
+/// How often progress is read, in ms, in the last moments of a CUE segment.
+const SEGMENT_END_POLL: u64 = 20;
+
+/// Time left in the current CUE segment in ms, if it ends before its file does.
+fn segment_remaining(player: &AudioPlayer) -> Option<u64> {
+    let track = &player.currently_playing.as_ref()?.track;
+    let left = track.end_offset? - track.start() - player.position;
+    Some((left.max(0.0) * 1000.0) as u64)
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug, Type, PartialEq)]
 pub enum PlayerState {
     Paused,
@@ -76,23 +305,241 @@ pub enum PlayerState {
     Stopped,
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug, Type)]
+#[derive(Clone, Serialize, Deserialize, Debug, Type, PartialEq, Default)]
 pub enum LoopType {
     LoopOnce,
     LoopOver,
+    #[default]
     Off,
 }
 
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Type, PartialEq, Default)]
+pub enum ShuffleStrategy {
+    /// Every order equally likely
+    #[default]
+    Uniform,
+    /// Tracks by the same artist and from the same album spread out evenly
+    Balanced,
+}
+
+/// A region of the current track that keeps repeating, in seconds.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Type, PartialEq)]
+pub struct AbLoop {
+    pub start: f64,
+    pub end: f64,
+}
+
+impl AbLoop {
+    /// A region within a track `duration` seconds long, 0 when it isn't known.
+    fn new(start: f64, end: f64, duration: f64) -> CommandResult<Self> {
+        if start < 0.0 || end <= start || (duration > 0.0 && end > duration) {
+            return Err(CommandError::invalid(format!(
+                "Invalid loop region {start}-{end}"
+            )));
+        }
+
+        Ok(Self { start, end })
+    }
+}
+
+/// Played tracks kept in the saved session, the rest is history in memory only.
+const MAX_SAVED_HISTORY: usize = 100;
+
+/// Queue edits that can be undone before the oldest one is forgotten.
+const MAX_QUEUE_UNDO: usize = 20;
+
+/// What an undoable queue edit did, so the UI can describe it.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Type, PartialEq)]
+pub enum QueueEdit {
+    Cleared,
+    Removed,
+    Moved,
+    Replaced,
+    Added,
+}
+
+/// Sent as `queue-undo-changed` whenever what can be undone or redone changes.
 #[derive(Clone, Serialize, Deserialize, Debug, Type)]
-pub struct AudioPlayer {
-    currently_playing: Option<FullTrack>,
+pub struct QueueUndo {
+    pub can_undo: bool,
+    pub can_redo: bool,
+    pub last_edit: Option<QueueEdit>, // <- What `undo_queue` would revert
+}
+
+/// The queue as it was before an edit.
+struct QueueSnapshot {
+    edit: QueueEdit,
     shuffle: bool,
-    state: PlayerState,
-    history: VecDeque<FullTrack>,
-    real_queue: VecDeque<FullTrack>,
-    queue: VecDeque<FullTrack>, // <- This is a proxy
-    position: f64,
-    looping: LoopType,
+    real_queue: VecDeque<QueueEntry>,
+    queue: VecDeque<QueueEntry>,
+    context: Option<PlaybackContext>,
+    context_tracks: Vec<i64>,
+    playing: Option<FullTrack>,
+}
+
+impl QueueSnapshot {
+    fn of(player: &AudioPlayer, edit: QueueEdit) -> Self {
+        Self {
+            edit,
+            shuffle: player.shuffle,
+            real_queue: player.real_queue.clone(),
+            queue: player.queue.clone(),
+            context: player.context.clone(),
+            context_tracks: player.context_tracks.clone(),
+            playing: player.currently_playing.clone(),
+        }
+    }
+
+    fn restore(self, player: &mut AudioPlayer) {
+        player.shuffle = self.shuffle;
+        player.real_queue = self.real_queue;
+        player.queue = self.queue;
+        player.context = self.context;
+        player.context_tracks = self.context_tracks;
+
+        // The track that got replaced comes back up next instead of cutting
+        // off what's playing now
+        if matches!(self.edit, QueueEdit::Replaced | QueueEdit::Cleared) {
+            let playing = self.playing.filter(|track| {
+                !player
+                    .currently_playing
+                    .as_ref()
+                    .is_some_and(|current| current.track.id == track.track.id)
+            });
+
+            if let Some(track) = playing {
+                let entry = QueueEntry::new(track);
+                player.real_queue.push_front(entry.clone());
+                player.queue.push_front(entry);
+            }
+        }
+    }
+
+    fn same_queue(&self, other: &QueueSnapshot) -> bool {
+        let ids =
+            |queue: &VecDeque<QueueEntry>| queue.iter().map(|e| e.entry_id).collect::<Vec<_>>();
+        ids(&self.queue) == ids(&other.queue) && self.context == other.context
+    }
+}
+
+/// Snapshots to step back and forth through, newest last.
+#[derive(Default)]
+pub struct QueueHistory {
+    undo: VecDeque<QueueSnapshot>,
+    redo: Vec<QueueSnapshot>,
+}
+
+impl QueueHistory {
+    fn push_undo(&mut self, snapshot: QueueSnapshot) {
+        self.undo.push_back(snapshot);
+        if self.undo.len() > MAX_QUEUE_UNDO {
+            self.undo.pop_front();
+        }
+    }
+
+    /// Keeps `snapshot` as the newest edit. Anything that could be redone is
+    /// gone now.
+    fn remember(&mut self, snapshot: QueueSnapshot) {
+        // The last edit didn't change anything, this one takes its place
+        if self
+            .undo
+            .back()
+            .is_some_and(|last| last.same_queue(&snapshot))
+        {
+            self.undo.pop_back();
+        }
+
+        self.push_undo(snapshot);
+        self.redo.clear();
+    }
+
+    fn status(&self) -> QueueUndo {
+        QueueUndo {
+            can_undo: !self.undo.is_empty(),
+            can_redo: !self.redo.is_empty(),
+            last_edit: self.undo.back().map(|s| s.edit),
+        }
+    }
+}
+
+/// Where the queue was built from. Lets the UI say what's playing and the
+/// queue follow its source when that changes.
+#[derive(Clone, Serialize, Deserialize, Debug, Type, PartialEq)]
+pub enum PlaybackContext {
+    Album { id: i64, name: String },
+    Artist { id: i64, name: String },
+    Playlist { id: i64, name: String },
+    Search { query: String },
+}
+
+impl PlaybackContext {
+    /// The tracks the source holds right now, in the order it lists them.
+    async fn tracks(&self) -> CommandResult<Vec<FullTrack>> {
+        match self {
+            PlaybackContext::Album { id, .. } => {
+                let tracks = library_service().lock()?.get_tracks_by_album(*id)?;
+                full_tracks(&tracks)
+            }
+            PlaybackContext::Artist { id, .. } => {
+                let library = library_service().lock()?;
+
+                let mut tracks = Vec::new();
+                for album in library.get_albums_by_artist(*id)? {
+                    if let Some(album_id) = album.id {
+                        tracks.extend(library.get_tracks_by_album(album_id)?);
+                    }
+                }
+                drop(library);
+
+                full_tracks(&tracks)
+            }
+            PlaybackContext::Playlist { id, .. } => {
+                Ok(library_service().lock()?.get_tracks_in_playlist(*id)?)
+            }
+            PlaybackContext::Search { query } => {
+                let results = media_lib_cmd::search(query.clone()).await?;
+                Ok(results.tracks.into_iter().map(|r| r.track).collect())
+            }
+        }
+    }
+}
+
+/// A track's place in the queue. The same track can be queued more than
+/// once, `entry_id` tells the copies apart.
+#[derive(Clone, Serialize, Deserialize, Debug, Type)]
+pub struct QueueEntry {
+    pub entry_id: u32,
+    #[serde(flatten)]
+    pub track: FullTrack,
+}
+
+impl QueueEntry {
+    pub fn new(track: FullTrack) -> Self {
+        static NEXT_ENTRY_ID: AtomicU32 = AtomicU32::new(1);
+
+        Self {
+            entry_id: NEXT_ENTRY_ID.fetch_add(1, Ordering::Relaxed),
+            track,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Type)]
+pub struct AudioPlayer {
+    pub currently_playing: Option<FullTrack>,
+    pub shuffle: bool,
+    pub state: PlayerState,
+    pub history: VecDeque<FullTrack>,
+    pub real_queue: VecDeque<QueueEntry>,
+    pub queue: VecDeque<QueueEntry>, // <- This is a proxy
+    pub position: f64,
+    pub looping: LoopType,
+    pub sleep_timer: Option<SleepTimer>,
+    pub shuffle_strategy: ShuffleStrategy,
+    pub autoplay: bool,
+    pub ab_loop: Option<AbLoop>,
+    pub context: Option<PlaybackContext>,
+    pub context_tracks: Vec<i64>, // <- The source's tracks when the queue last followed it
 }
 
 impl Default for AudioPlayer {
@@ -106,6 +553,156 @@ impl Default for AudioPlayer {
             queue: VecDeque::new(),
             position: 0.0,
             looping: LoopType::Off,
+            sleep_timer: None,
+            shuffle_strategy: ShuffleStrategy::Uniform,
+            autoplay: false,
+            ab_loop: None,
+            context: None,
+            context_tracks: Vec::new(),
+        }
+    }
+}
+
+impl AudioPlayer {
+    /// Refills the queue with everything that has been played so far, so a
+    /// session on `LoopOver` wraps back around to its first track.
+    /// Expects the current track to already be at the back of `history`.
+    fn wrap_queue(&mut self) {
+        self.real_queue = self.history.drain(..).map(QueueEntry::new).collect();
+        self.queue = self.real_queue.clone();
+
+        if self.shuffle {
+            self.shuffle_queue();
+        }
+    }
+
+    /// Rebuilds the proxy queue as a shuffled copy of the real one.
+    fn shuffle_queue(&mut self) {
+        self.queue.clone_from(&self.real_queue);
+
+        match self.shuffle_strategy {
+            ShuffleStrategy::Uniform => self.queue.shuffle(),
+            ShuffleStrategy::Balanced => self
+                .queue
+                .balanced_shuffle(|e| e.track.track.artist_id, |e| e.track.track.album_id),
+        }
+    }
+
+    /// The track the player will move on to once the current one ends, if
+    /// it can be known ahead of time.
+    fn upcoming(&self) -> Option<&FullTrack> {
+        match self.looping {
+            LoopType::LoopOnce => self.currently_playing.as_ref(),
+            LoopType::LoopOver if self.queue.is_empty() && !self.shuffle => {
+                self.history.front().or(self.currently_playing.as_ref())
+            }
+            _ => self.queue.front().map(|e| &e.track),
+        }
+    }
+
+    /// Applies what the context's source gained and lost since the queue last
+    /// followed it. New tracks go in after the track that precedes them in the
+    /// source, or anywhere when shuffling. Returns whether the queue changed.
+    fn follow_context(&mut self, tracks: &[FullTrack]) -> bool {
+        let known: HashSet<i64> = self.context_tracks.iter().copied().collect();
+        let now: HashSet<i64> = track_ids(tracks).into_iter().collect();
+        if known == now {
+            return false;
+        }
+        self.context_tracks = track_ids(tracks);
+
+        let removed = |e: &QueueEntry| {
+            e.track
+                .track
+                .id
+                .is_some_and(|id| known.contains(&id) && !now.contains(&id))
+        };
+        self.real_queue.retain(|e| !removed(e));
+        self.queue.retain(|e| !removed(e));
+
+        // In order, whatever was added before the current track is already behind
+        let current = self.currently_playing.as_ref().and_then(|t| t.track.id);
+        let start = current
+            .filter(|_| !self.shuffle)
+            .and_then(|id| tracks.iter().position(|t| t.track.id == Some(id)))
+            .map_or(0, |position| position + 1);
+
+        for (i, track) in tracks.iter().enumerate().skip(start) {
+            if track.track.id.is_none_or(|id| known.contains(&id)) {
+                continue;
+            }
+
+            let entry = QueueEntry::new(track.clone());
+            let after = tracks[start..i].iter().rev().find_map(|prev| {
+                self.real_queue
+                    .iter()
+                    .rposition(|e| e.track.track.id == prev.track.id)
+            });
+            match after {
+                Some(index) => self.real_queue.insert(index + 1, entry.clone()),
+                None => self.real_queue.push_front(entry.clone()),
+            }
+
+            if self.shuffle {
+                let index = (rand::random::<f64>() * (self.queue.len() + 1) as f64) as usize;
+                self.queue.insert(index.min(self.queue.len()), entry);
+            }
+        }
+
+        if !self.shuffle {
+            self.queue = self.real_queue.clone();
+        }
+
+        true
+    }
+
+    fn to_session(&self) -> Session {
+        Session {
+            currently_playing: self.currently_playing.as_ref().and_then(|t| t.track.id),
+            history: session::track_ids(
+                self.history
+                    .iter()
+                    .skip(self.history.len().saturating_sub(MAX_SAVED_HISTORY)),
+            ),
+            real_queue: session::entry_ids(&self.real_queue),
+            queue: session::entry_ids(&self.queue),
+            shuffle: self.shuffle,
+            position: self.position,
+            looping: self.looping.clone(),
+            shuffle_strategy: self.shuffle_strategy,
+            autoplay: self.autoplay,
+            context: self.context.clone(),
+            context_tracks: self.context_tracks.clone(),
+        }
+    }
+
+    fn from_session(saved: Session) -> Self {
+        let currently_playing = saved
+            .currently_playing
+            .and_then(|id| session::resolve(&[id]).pop_front());
+        let (real_queue, queue) = session::resolve_queue(&saved.real_queue, &saved.queue);
+
+        AudioPlayer {
+            state: match currently_playing {
+                Some(_) => PlayerState::Paused,
+                None => PlayerState::Empty,
+            },
+            position: match currently_playing {
+                Some(_) => saved.position,
+                None => 0.0,
+            },
+            currently_playing,
+            shuffle: saved.shuffle,
+            history: session::resolve(&saved.history),
+            real_queue,
+            queue,
+            looping: saved.looping,
+            sleep_timer: None,
+            shuffle_strategy: saved.shuffle_strategy,
+            autoplay: saved.autoplay,
+            ab_loop: None,
+            context: saved.context,
+            context_tracks: saved.context_tracks,
         }
     }
 }
@@ -116,133 +713,758 @@ pub async fn play_tracks(
     state: tauri::State<'_, ManagedPlayer>,
     tracks: Vec<Track>,
     index: i32,
-) -> Result<AudioPlayer, String> {
-    let mut full_tracks: Vec<FullTrack> = Vec::new();
-
-    if let Ok(service) = library_service().lock() {
-        //Convert tracks to fulltrack
-        for track in tracks {
-            if let Ok(ft_option) = service.get_full_track_by_id(track.id.unwrap()) {
-                if let Some(full_track) = ft_option {
-                    full_tracks.push(full_track);
-                }
-            }
-        }
-    }
+) -> CommandResult<AudioPlayer> {
+    let full_tracks = full_tracks(&tracks)?;
 
     if !full_tracks.is_empty() {
-        _ = play_list(state.clone(), full_tracks, index).await;
+        play_list(state.clone(), full_tracks, index).await?;
     }
 
     Ok(state.get().await)
 }
 
+/// Looks up the library entries for tracks the frontend passed in, skipping
+/// any that are no longer in the library.
+fn full_tracks(tracks: &[Track]) -> CommandResult<Vec<FullTrack>> {
+    let library = library_service().lock()?;
+
+    let mut full_tracks = Vec::new();
+    for id in tracks.iter().filter_map(|t| t.id) {
+        if let Some(full_track) = library.get_full_track_by_id(id)? {
+            full_tracks.push(full_track);
+        }
+    }
+
+    Ok(full_tracks)
+}
+
 #[tauri::command]
 #[specta::specta]
-pub async fn get_player(state: tauri::State<'_, ManagedPlayer>) -> Result<AudioPlayer, String> {
+pub async fn get_player(state: tauri::State<'_, ManagedPlayer>) -> CommandResult<AudioPlayer> {
     Ok(state.get().await)
 }
 
+/// Loads `track` paused. A track that fails to load is reported and flagged
+/// in the library before the error is handed back.
 #[tauri::command]
 #[specta::specta]
 pub async fn load(
     state: tauri::State<'_, ManagedPlayer>,
     track: FullTrack,
-) -> Result<AudioPlayer, String> {
-    let audio_engine = audio_player().lock().await;
-    _ = audio_engine.clone().load(&track.track.file_path).await;
+) -> CommandResult<AudioPlayer> {
+    if let Err(e) = open_track(&state, track.clone()).await {
+        unplayable(&state, &track, &e);
+        return Err(e);
+    }
+
+    Ok(state.get().await)
+}
+
+async fn open_track(state: &ManagedPlayer, track: FullTrack) -> CommandResult<()> {
+    state.next_segment.lock().await.take();
+    remember_current(state).await;
+
+    let resume = bookmarks::resume_position(state, &track).await;
+
+    {
+        let audio_engine = audio_player().lock().await;
+        audio_engine
+            .clone()
+            .load(track.track.audio_path())
+            .await
+            .map_err(CommandError::engine)?;
+
+        let offset = track.track.start() + resume.unwrap_or(0.0);
+        if offset > 0.0 {
+            audio_engine
+                .seek(offset)
+                .await
+                .map_err(CommandError::engine)?;
+        }
+    }
+
+    // It plays again, so whatever broke it has been fixed
+    if let (Some(id), Some(_)) = (track.track.id, &track.track.playback_error) {
+        if let Ok(library) = library_service().lock() {
+            _ = library.set_playback_error(id, None);
+        }
+    }
+
+    play_tracker::start(state, &track, resume.unwrap_or(0.0)).await;
+    scrobbler::now_playing(&state.app, &track);
 
     state
         .update(|player| {
             player.currently_playing = Some(track);
             player.state = PlayerState::Paused;
+            player.position = resume.unwrap_or(0.0);
+            player.ab_loop = None;
         })
         .await;
 
-    Ok(state.get().await)
+    Ok(())
+}
+
+/// Saves how far into the current track playback got, for tracks that resume.
+async fn remember_current(state: &ManagedPlayer) {
+    let current = state.player.lock().await.currently_playing.clone();
+    if let Some(current) = current {
+        if let Some(position) = current_progress(state).await {
+            bookmarks::remember_position(state, &current, position).await;
+        }
+    }
+}
+
+/// Stops on an entry that couldn't be played. It goes back to the front of
+/// the queue, so nothing that was queued is lost.
+async fn stop_on(state: &ManagedPlayer, track: FullTrack) -> CommandResult<()> {
+    state
+        .update(|player| {
+            let entry = QueueEntry::new(track);
+            player.real_queue.push_front(entry.clone());
+            player.queue.push_front(entry);
+
+            player.currently_playing = None;
+            player.state = PlayerState::Empty;
+            player.position = 0.0;
+            player.ab_loop = None;
+
+            state.update_queue(&player);
+        })
+        .await;
+
+    let audio_engine = audio_player().lock().await;
+    audio_engine.clear().await.map_err(CommandError::engine)
+}
+
+/// Payload of `playback-error`.
+#[derive(Clone, Serialize, Debug, Type)]
+pub struct PlaybackError {
+    pub track: FullTrack,
+    pub reason: String,
+}
+
+/// Tells the frontend a track couldn't be played and remembers why in the
+/// library, where broken files can be reviewed later.
+fn unplayable(state: &ManagedPlayer, track: &FullTrack, error: &CommandError) {
+    let reason = error.to_string();
+    eprintln!("Failed to load {}: {}", track.track.file_path, reason);
+
+    if let (Ok(library), Some(id)) = (library_service().lock(), track.track.id) {
+        _ = library.set_playback_error(id, Some(&reason));
+    }
+
+    _ = state.app.emit(
+        "playback-error",
+        PlaybackError {
+            track: track.clone(),
+            reason,
+        },
+    );
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn play(state: tauri::State<'_, ManagedPlayer>) -> Result<AudioPlayer, String> {
+pub async fn play(state: tauri::State<'_, ManagedPlayer>) -> CommandResult<AudioPlayer> {
     let playerstate = state.get().await.state;
 
     if (playerstate != PlayerState::Playing) && (playerstate != PlayerState::Empty) {
+        {
+            let player = audio_player().lock().await;
+            player.play().await.map_err(CommandError::engine)?;
+        }
+
         state
             .update(|s| {
                 s.state = PlayerState::Playing;
             })
             .await;
+    }
+
+    Ok(state.get().await)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn pause(state: tauri::State<'_, ManagedPlayer>) -> CommandResult<AudioPlayer> {
+    let player = state.get().await;
+
+    if player.state != PlayerState::Paused {
+        {
+            let audio_engine = audio_player().lock().await;
+            audio_engine.pause().await.map_err(CommandError::engine)?;
+        }
+
+        state
+            .update(|s| {
+                s.state = PlayerState::Paused;
+            })
+            .await;
+
+        if let Some(position) = current_progress(&state).await {
+            state
+                .update(|s| {
+                    s.position = position;
+                })
+                .await;
+
+            if let Some(track) = player.currently_playing {
+                bookmarks::remember_position(&state, &track, position).await;
+            }
+        }
+    }
+
+    Ok(state.get().await)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn clear(state: tauri::State<'_, ManagedPlayer>) -> CommandResult<AudioPlayer> {
+    state.remember_queue(QueueEdit::Cleared).await;
+
+    state
+        .update(|player| {
+            player.currently_playing = None;
+            player.ab_loop = None;
+            player.state = PlayerState::Empty;
+            player.real_queue.clear();
+            player.queue.clear();
+            player.context = None;
+            player.context_tracks.clear();
+
+            state.update_queue(&player);
+        })
+        .await;
+
+    let audio_engine = audio_player().lock().await;
+    audio_engine.clear().await.map_err(CommandError::engine)?;
+    drop(audio_engine);
+
+    Ok(state.get().await)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn play_list(
+    state: tauri::State<'_, ManagedPlayer>,
+    mut list: Vec<FullTrack>,
+    idx: i32,
+) -> CommandResult<AudioPlayer> {
+    let mut index = idx as usize;
+    if idx < 0 || index >= list.len() {
+        return Err(CommandError::InvalidIndex(index));
+    }
+
+    let first_track = list.remove(index);
+
+    state.remember_queue(QueueEdit::Replaced).await;
+
+    // The first track is queued too, then played like any next track so
+    // one that won't load is skipped over
+    state
+        .update(|player| {
+            player.real_queue.clear();
+            player.real_queue.push_back(QueueEntry::new(first_track));
+
+            while index < list.len() {
+                player
+                    .real_queue
+                    .push_back(QueueEntry::new(list[index].clone()));
+                index += 1;
+            }
+
+            player.queue = player.real_queue.clone();
+            player.context = None;
+            player.context_tracks.clear();
+            state.update_queue(&player);
+        })
+        .await;
+
+    advance(state.clone(), false).await?;
+
+    Ok(state.get().await)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn play_album(
+    state: tauri::State<'_, ManagedPlayer>,
+    id: i64,
+    start_index: Option<u32>,
+    shuffle: bool,
+) -> CommandResult<AudioPlayer> {
+    let album = library_service()
+        .lock()?
+        .get_album_by_id(id)?
+        .ok_or_else(|| CommandError::not_found(format!("Album {id}")))?;
+
+    let context = PlaybackContext::Album {
+        id,
+        name: album.title,
+    };
+    play_context(state, context, start_index, shuffle).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn play_artist(
+    state: tauri::State<'_, ManagedPlayer>,
+    id: i64,
+    start_index: Option<u32>,
+    shuffle: bool,
+) -> CommandResult<AudioPlayer> {
+    let artist = library_service()
+        .lock()?
+        .get_artist_by_id(id)?
+        .ok_or_else(|| CommandError::not_found(format!("Artist {id}")))?;
+
+    let context = PlaybackContext::Artist {
+        id,
+        name: artist.name,
+    };
+    play_context(state, context, start_index, shuffle).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn play_playlist(
+    state: tauri::State<'_, ManagedPlayer>,
+    id: i64,
+    start_index: Option<u32>,
+    shuffle: bool,
+) -> CommandResult<AudioPlayer> {
+    let playlist = library_service()
+        .lock()?
+        .get_playlist_by_id(id)?
+        .ok_or_else(|| CommandError::not_found(format!("Playlist {id}")))?;
+
+    let context = PlaybackContext::Playlist {
+        id,
+        name: playlist.name,
+    };
+    play_context(state, context, start_index, shuffle).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn play_search(
+    state: tauri::State<'_, ManagedPlayer>,
+    query: String,
+    start_index: Option<u32>,
+    shuffle: bool,
+) -> CommandResult<AudioPlayer> {
+    let query = query.trim().to_string();
+    if query.is_empty() {
+        return Err(CommandError::invalid("Empty search query"));
+    }
+
+    play_context(
+        state,
+        PlaybackContext::Search { query },
+        start_index,
+        shuffle,
+    )
+    .await
+}
+
+/// Replaces the queue with everything in `context`, starting at `start_index`.
+/// Shuffling without a starting point lets the shuffle pick the first track.
+async fn play_context(
+    state: tauri::State<'_, ManagedPlayer>,
+    context: PlaybackContext,
+    start_index: Option<u32>,
+    shuffle: bool,
+) -> CommandResult<AudioPlayer> {
+    let mut tracks = context.tracks().await?;
+    if tracks.is_empty() {
+        return Err(CommandError::not_found("Nothing to play"));
+    }
+    let context_tracks = track_ids(&tracks);
+
+    let first_track = match start_index {
+        Some(index) if index as usize >= tracks.len() => {
+            return Err(CommandError::InvalidIndex(index as usize));
+        }
+        Some(index) => Some(tracks.remove(index as usize)),
+        None if shuffle => None,
+        None => Some(tracks.remove(0)),
+    };
+
+    state.remember_queue(QueueEdit::Replaced).await;
+
+    state
+        .update(|player| {
+            player.shuffle = shuffle;
+            player.real_queue = tracks.into_iter().map(QueueEntry::new).collect();
+
+            if shuffle {
+                player.shuffle_queue();
+            } else {
+                player.queue = player.real_queue.clone();
+            }
+
+            // Without a starting track the shuffled queue's first entry plays
+            if let Some(track) = first_track {
+                let entry = QueueEntry::new(track);
+                player.real_queue.push_front(entry.clone());
+                player.queue.push_front(entry);
+            }
+
+            player.context = Some(context);
+            player.context_tracks = context_tracks;
+            state.update_queue(&player);
+        })
+        .await;
+
+    // Played like any next track, so one that won't load is skipped over
+    advance(state.clone(), false).await?;
+
+    Ok(state.get().await)
+}
+
+fn track_ids(tracks: &[FullTrack]) -> Vec<i64> {
+    tracks.iter().filter_map(|t| t.track.id).collect()
+}
+
+/// Keeps the queue in step with the source it was built from after that
+/// changed, e.g. tracks were added to the playlist that's playing. Only the
+/// tracks the source gained or lost since are queued or dropped, so entries
+/// queued by hand and tracks taken out of the queue stay as they are.
+pub async fn refresh_context(state: &ManagedPlayer, affected: impl Fn(&PlaybackContext) -> bool) {
+    let Some(context) = state.player.lock().await.context.clone() else {
+        return;
+    };
+    if !affected(&context) {
+        return;
+    }
+
+    let tracks = match context.tracks().await {
+        Ok(tracks) => tracks,
+        Err(e) => {
+            eprintln!("Failed to refresh queue from {:?}: {:?}", context, e);
+            return;
+        }
+    };
+
+    state
+        .update(|player| {
+            // Something else replaced the queue in the meantime
+            if player.context.as_ref() != Some(&context) {
+                return;
+            }
+
+            // The source is gone, what's queued stays but no longer follows it
+            if tracks.is_empty() {
+                player.context = None;
+                player.context_tracks.clear();
+                return;
+            }
+
+            if player.follow_context(&tracks) {
+                state.update_queue(&player);
+            }
+        })
+        .await;
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn play_next(
+    state: tauri::State<'_, ManagedPlayer>,
+    track: FullTrack,
+) -> CommandResult<AudioPlayer> {
+    let entry = QueueEntry::new(track);
+
+    state
+        .update(|player| {
+            player.real_queue.push_front(entry.clone());
+            player.queue.push_front(entry);
+            state.update_queue(&player);
+        })
+        .await;
+
+    Ok(state.get().await)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn add_to_queue(
+    state: tauri::State<'_, ManagedPlayer>,
+    track: FullTrack,
+) -> CommandResult<AudioPlayer> {
+    let entry = QueueEntry::new(track);
+
+    state
+        .update(|player| {
+            player.real_queue.push_back(entry.clone());
+            player.queue.push_back(entry);
+            state.update_queue(&player);
+        })
+        .await;
+
+    Ok(state.get().await)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn add_list_to_queue(
+    state: tauri::State<'_, ManagedPlayer>,
+    fulltracks: Option<Vec<FullTrack>>,
+    tracks: Option<Vec<Track>>,
+) -> CommandResult<AudioPlayer> {
+    state.remember_queue(QueueEdit::Added).await;
+
+    if let Some(fulltracks) = fulltracks {
+        for track in fulltracks {
+            add_to_queue(state.clone(), track).await?;
+        }
+    }
+
+    if let Some(tracks) = tracks {
+        for track in full_tracks(&tracks)? {
+            add_to_queue(state.clone(), track).await?;
+        }
+    }
+
+    Ok(state.get().await)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn play_list_next(
+    state: tauri::State<'_, ManagedPlayer>,
+    fulltracks: Option<Vec<FullTrack>>,
+    tracks: Option<Vec<Track>>,
+) -> CommandResult<AudioPlayer> {
+    state.remember_queue(QueueEdit::Added).await;
+
+    if let Some(fulltracks) = fulltracks {
+        for track in fulltracks {
+            play_next(state.clone(), track).await?;
+        }
+    }
+
+    if let Some(tracks) = tracks {
+        for track in full_tracks(&tracks)? {
+            play_next(state.clone(), track).await?;
+        }
+    }
+
+    Ok(state.get().await)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn next(state: tauri::State<'_, ManagedPlayer>) -> CommandResult<AudioPlayer> {
+    play_tracker::skipped(&state).await;
+    advance(state.clone(), false).await?;
+
+    Ok(state.get().await)
+}
+
+/// Unplayable tracks in a row before giving up, so a queue full of them
+/// can't keep the player busy forever when it loops.
+const MAX_SKIPPED: usize = 10;
+
+/// Moves on to the next queue item, skipping over any that fail to load.
+/// `continued` means the engine already played on into it by itself, so only
+/// the player state has to follow. Returns false when there was nothing to
+/// move on to. When nothing would load, the player stops on the last entry
+/// that failed and that entry's error is returned.
+async fn advance(state: tauri::State<'_, ManagedPlayer>, continued: bool) -> CommandResult<bool> {
+    if !continued {
+        remember_current(&state).await;
+    }
+
+    let mut radio = radio_tracks(&state).await;
+    let mut failed = None;
+
+    for _ in 0..MAX_SKIPPED {
+        let mut track: Option<FullTrack> = None;
+
+        state
+            .update(|player| {
+                if player.queue.is_empty() && player.looping == LoopType::LoopOver {
+                    if let Some(current) = player.currently_playing.take() {
+                        player.history.push_back(current);
+                    }
+                    player.wrap_queue();
+                }
+
+                if player.queue.is_empty() {
+                    for t in radio.drain(..) {
+                        let entry = QueueEntry::new(t);
+                        player.real_queue.push_back(entry.clone());
+                        player.queue.push_back(entry);
+                    }
+                }
+
+                let Some(entry) = player.queue.pop_front() else {
+                    return;
+                };
+                player.real_queue.retain(|e| e.entry_id != entry.entry_id);
+                track = Some(entry.track);
+
+                // Taken rather than copied so a track that fails to load
+                // doesn't send its predecessor to the history twice
+                if let Some(current) = player.currently_playing.take() {
+                    player.history.push_back(current);
+                }
+
+                state.update_queue(&player);
+                state.update_history(&player);
+            })
+            .await;
+
+        match track {
+            Some(t) if continued => {
+                play_tracker::start(&state, &t, 0.0).await;
+                scrobbler::now_playing(&state.app, &t);
+
+                state
+                    .update(|player| {
+                        player.currently_playing = Some(t);
+                        player.state = PlayerState::Playing;
+                        player.ab_loop = None;
+                    })
+                    .await;
+
+                return Ok(true);
+            }
+            Some(t) => match open_track(&state, t.clone()).await {
+                Ok(()) => {
+                    play(state.clone()).await?;
+                    return Ok(true);
+                }
+                Err(e) => {
+                    unplayable(&state, &t, &e);
+                    failed = Some((t, e));
+                }
+            },
+            None => break,
+        }
+    }
+
+    match failed {
+        Some((track, e)) => {
+            stop_on(&state, track).await?;
+            Err(e)
+        }
+        None => Ok(false),
+    }
+}
+
+/// How far back the history is checked so autoplay doesn't repeat songs.
+const AUTOPLAY_RECENT: usize = 100;
+
+/// Tracks to keep going with once the queue has run out, if autoplay is on.
+async fn radio_tracks(state: &ManagedPlayer) -> Vec<FullTrack> {
+    let (seed, recent) = {
+        let player = state.player.lock().await;
+
+        if !player.autoplay || !player.queue.is_empty() || player.looping == LoopType::LoopOver {
+            return Vec::new();
+        }
+
+        let Some(seed) = player.currently_playing.clone() else {
+            return Vec::new();
+        };
 
-        let player = audio_player().lock().await;
-        _ = player.play().await;
-    }
+        let recent: HashSet<i64> = player
+            .history
+            .iter()
+            .rev()
+            .take(AUTOPLAY_RECENT)
+            .chain(std::iter::once(&seed))
+            .filter_map(|t| t.track.id)
+            .collect();
 
-    Ok(state.get().await)
+        (seed, recent)
+    };
+
+    radio::similar_tracks(&seed, &recent)
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn pause(state: tauri::State<'_, ManagedPlayer>) -> Result<AudioPlayer, String> {
-    let player = state.get().await;
-
-    if player.state != PlayerState::Paused {
-        state
-            .update(|s| {
-                s.state = PlayerState::Paused;
-            })
-            .await;
+pub async fn previous(state: tauri::State<'_, ManagedPlayer>) -> CommandResult<AudioPlayer> {
+    let (track, current) = {
+        let player = state.player.lock().await;
+        (
+            player.history.back().cloned(),
+            player.currently_playing.clone(),
+        )
+    };
 
-        let audio_engine = audio_player().lock().await;
-        _ = audio_engine.pause().await;
-    }
+    let Some(track) = track else {
+        return Ok(state.get().await);
+    };
 
-    Ok(state.get().await)
-}
+    // The queue only changes once the track has loaded
+    load(state.clone(), track).await?;
 
-#[tauri::command]
-#[specta::specta]
-pub async fn clear(state: tauri::State<'_, ManagedPlayer>) -> Result<AudioPlayer, String> {
     state
         .update(|player| {
-            player.currently_playing = None;
-            player.state = PlayerState::Empty;
-            player.real_queue.clear();
-            player.queue.clear();
+            player.history.pop_back();
+
+            if let Some(current) = current {
+                let entry = QueueEntry::new(current);
+                player.queue.push_front(entry.clone());
+                player.real_queue.push_front(entry);
+            }
 
             state.update_queue(&player);
+            state.update_history(&player);
         })
         .await;
 
-    let audio_engine = audio_player().lock().await;
-    _ = audio_engine.clear().await;
+    play(state.clone()).await?;
 
     Ok(state.get().await)
 }
 
+/// Moves a queue entry to `new_idx`, given as a position in the queue
+/// before the entry is taken out.
 #[tauri::command]
 #[specta::specta]
-pub async fn play_list(
+pub async fn change_queue_index(
     state: tauri::State<'_, ManagedPlayer>,
-    mut list: Vec<FullTrack>,
-    idx: i32,
-) -> Result<AudioPlayer, String> {
-    let mut index = idx as usize;
+    entry_id: u32,
+    new_idx: i32,
+) -> CommandResult<AudioPlayer> {
+    if !state
+        .player
+        .lock()
+        .await
+        .queue
+        .iter()
+        .any(|e| e.entry_id == entry_id)
+    {
+        return Err(CommandError::not_found(format!("Queue entry {entry_id}")));
+    }
 
-    let first_track = list.remove(index);
-    _ = load(state.clone(), first_track).await;
-    _ = play(state.clone()).await;
+    state.remember_queue(QueueEdit::Moved).await;
 
     state
         .update(|player| {
-            player.real_queue.clear();
+            let Some(old_index) = player.queue.iter().position(|e| e.entry_id == entry_id) else {
+                return;
+            };
 
-            while index < list.len() {
-                player.real_queue.push_back(list[index].clone());
-                index += 1;
+            let mut new_index = (new_idx.max(0) as usize).min(player.queue.len());
+            if old_index < new_index {
+                new_index -= 1;
+            }
+
+            if let Some(entry) = player.queue.remove(old_index) {
+                player.queue.insert(new_index, entry);
+            }
+
+            // Without shuffle both queues are in the same order
+            if !player.shuffle {
+                player.real_queue = player.queue.clone();
             }
 
-            player.queue = player.real_queue.clone();
             state.update_queue(&player);
         })
         .await;
@@ -252,31 +1474,77 @@ pub async fn play_list(
 
 #[tauri::command]
 #[specta::specta]
-pub async fn play_next(
-    state: tauri::State<'_, ManagedPlayer>,
-    track: FullTrack,
-) -> Result<AudioPlayer, String> {
+pub async fn undo_queue(state: tauri::State<'_, ManagedPlayer>) -> CommandResult<AudioPlayer> {
+    step_queue(&state, true).await?;
+    Ok(state.get().await)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn redo_queue(state: tauri::State<'_, ManagedPlayer>) -> CommandResult<AudioPlayer> {
+    step_queue(&state, false).await?;
+    Ok(state.get().await)
+}
+
+/// Puts back the queue from one end of the history, keeping the current one
+/// on the other so the step can be reversed. What's playing is left alone.
+async fn step_queue(state: &ManagedPlayer, undo: bool) -> CommandResult<()> {
+    let mut history = state.queue_history.lock().await;
+
+    let snapshot = if undo {
+        history.undo.pop_back()
+    } else {
+        history.redo.pop()
+    };
+    let Some(snapshot) = snapshot else {
+        return Err(CommandError::not_found("Queue edit to step to"));
+    };
+
+    let mut current = None;
     state
         .update(|player| {
-            player.real_queue.push_front(track.clone());
-            player.queue.push_front(track);
+            current = Some(QueueSnapshot::of(player, snapshot.edit));
+            snapshot.restore(player);
             state.update_queue(&player);
         })
         .await;
 
-    Ok(state.get().await)
+    if let Some(current) = current {
+        if undo {
+            history.redo.push(current);
+        } else {
+            history.push_undo(current);
+        }
+    }
+
+    let _ = state.app.emit("queue-undo-changed", history.status());
+    Ok(())
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn add_to_queue(
+pub async fn remove_from_queue(
     state: tauri::State<'_, ManagedPlayer>,
-    track: FullTrack,
-) -> Result<AudioPlayer, String> {
+    entry_id: u32,
+) -> CommandResult<AudioPlayer> {
+    if !state
+        .player
+        .lock()
+        .await
+        .queue
+        .iter()
+        .any(|e| e.entry_id == entry_id)
+    {
+        return Err(CommandError::not_found(format!("Queue entry {entry_id}")));
+    }
+
+    state.remember_queue(QueueEdit::Removed).await;
+
     state
         .update(|player| {
-            player.real_queue.push_back(track.clone());
-            player.queue.push_back(track);
+            player.real_queue.retain(|e| e.entry_id != entry_id);
+            player.queue.retain(|e| e.entry_id != entry_id);
+
             state.update_queue(&player);
         })
         .await;
@@ -284,202 +1552,188 @@ pub async fn add_to_queue(
     Ok(state.get().await)
 }
 
+/// Jumps straight to a queue entry. Everything queued before it counts as
+/// played and moves to the history.
 #[tauri::command]
 #[specta::specta]
-pub async fn add_list_to_queue(
+pub async fn skip_to_entry(
     state: tauri::State<'_, ManagedPlayer>,
-    fulltracks: Option<Vec<FullTrack>>,
-    tracks: Option<Vec<Track>>,
-) -> Result<AudioPlayer, String> {
-    if let Some(fulltracks) = fulltracks {
-        for track in fulltracks {
-            _ = add_to_queue(state.clone(), track).await;
-        }
+    entry_id: u32,
+) -> CommandResult<AudioPlayer> {
+    if !state
+        .player
+        .lock()
+        .await
+        .queue
+        .iter()
+        .any(|e| e.entry_id == entry_id)
+    {
+        return Err(CommandError::not_found(format!("Queue entry {entry_id}")));
     }
 
-    if let Some(tracks) = tracks {
-        for track in tracks {
-            let mut temp_track: Option<FullTrack> = None;
+    play_tracker::skipped(&state).await;
+    remember_current(&state).await;
 
-            if let Ok(library) = library_service().lock() {
-                if let Ok(res) = library.get_full_track_by_id(track.id.unwrap()) {
-                    if let Some(fulltrack) = res {
-                        temp_track = Some(fulltrack);
-                    }
-                }
+    // Everything up to the entry goes to the history, then it's played like
+    // any next track so one that won't load is skipped over
+    state
+        .update(|player| {
+            if !player.queue.iter().any(|e| e.entry_id == entry_id) {
+                return;
             }
 
-            if let Some(track) = temp_track {
-                _ = add_to_queue(state.clone(), track).await;
+            if let Some(current) = player.currently_playing.take() {
+                player.history.push_back(current);
             }
-        }
-    }
+
+            while player.queue.front().is_some_and(|e| e.entry_id != entry_id) {
+                if let Some(entry) = player.queue.pop_front() {
+                    player.real_queue.retain(|e| e.entry_id != entry.entry_id);
+                    player.history.push_back(entry.track);
+                }
+            }
+
+            state.update_queue(&player);
+            state.update_history(&player);
+        })
+        .await;
+
+    advance(state.clone(), false).await?;
 
     Ok(state.get().await)
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn play_list_next(
-    state: tauri::State<'_, ManagedPlayer>,
-    fulltracks: Option<Vec<FullTrack>>,
-    tracks: Option<Vec<Track>>,
-) -> Result<AudioPlayer, String> {
-    if let Some(fulltracks) = fulltracks {
-        for track in fulltracks {
-            _ = play_next(state.clone(), track).await;
-        }
-    }
+pub async fn seek(state: tauri::State<'_, ManagedPlayer>, time: f64) -> CommandResult<()> {
+    let start = state
+        .player
+        .lock()
+        .await
+        .currently_playing
+        .as_ref()
+        .map_or(0.0, |t| t.track.start());
 
-    if let Some(tracks) = tracks {
-        for track in tracks {
-            let mut temp_track: Option<FullTrack> = None;
+    {
+        let audio_engine = audio_player().lock().await;
+        audio_engine
+            .seek(start + time)
+            .await
+            .map_err(CommandError::engine)?;
+    }
 
-            if let Ok(library) = library_service().lock() {
-                if let Ok(res) = library.get_full_track_by_id(track.id.unwrap()) {
-                    if let Some(fulltrack) = res {
-                        temp_track = Some(fulltrack);
-                    }
-                }
-            }
+    // The progress publisher is idle while paused
+    state.player.lock().await.position = time;
+    _ = state.app.emit("progress-changed", time);
 
-            if let Some(track) = temp_track {
-                _ = play_next(state.clone(), track).await;
-            }
-        }
-    }
+    #[cfg(target_os = "linux")]
+    crate::mpris::seeked(time);
 
-    Ok(state.get().await)
+    Ok(())
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn next(state: tauri::State<'_, ManagedPlayer>) -> Result<AudioPlayer, String> {
-    let mut track: Option<FullTrack> = None;
-
+pub async fn shuffle(state: tauri::State<'_, ManagedPlayer>) -> CommandResult<AudioPlayer> {
     state
         .update(|player| {
-            if let Some(current) = player.currently_playing.clone() {
-                player.history.push_back(current);
-            }
-
-            track = player.queue.pop_front();
-
-            if let Some(ref t) = track {
-                player
-                    .real_queue
-                    .retain(|m| m.track.id.unwrap_or(-1) != t.track.id.unwrap_or(-2));
+            if player.shuffle {
+                player.queue.clone_from(&player.real_queue);
+            } else {
+                player.shuffle_queue();
             }
-
+            player.shuffle = !player.shuffle;
             state.update_queue(&player);
-            state.update_history(&player);
         })
         .await;
 
-    if let Some(t) = track {
-        _ = load(state.clone(), t).await;
-        _ = play(state.clone()).await;
-    }
-
     Ok(state.get().await)
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn previous(state: tauri::State<'_, ManagedPlayer>) -> Result<AudioPlayer, String> {
-    let mut track: Option<FullTrack> = None;
-
+pub async fn set_shuffle_strategy(
+    state: tauri::State<'_, ManagedPlayer>,
+    strategy: ShuffleStrategy,
+) -> CommandResult<AudioPlayer> {
     state
         .update(|player| {
-            track = player.history.pop_back();
-
-            if track.is_some() {
-                if let Some(current) = player.currently_playing.clone() {
-                    player.queue.push_front(current.clone());
-                    player.real_queue.push_front(current);
-                }
+            player.shuffle_strategy = strategy;
 
+            if player.shuffle {
+                player.shuffle_queue();
                 state.update_queue(&player);
-                state.update_history(&player);
             }
         })
         .await;
 
-    if let Some(t) = track {
-        _ = load(state.clone(), t).await;
-        _ = play(state.clone()).await;
-    }
-
     Ok(state.get().await)
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn change_queue_index(
+pub async fn set_loop_mode(
     state: tauri::State<'_, ManagedPlayer>,
-    old_idx: i32,
-    new_idx: i32,
-) -> Result<AudioPlayer, String> {
-    let old_index = old_idx as usize;
-    let mut new_index = new_idx as usize;
-
+    mode: LoopType,
+) -> CommandResult<AudioPlayer> {
     state
         .update(|player| {
-            if old_index < new_index {
-                new_index -= 1;
-            }
-
-            if let Some(track) = player.queue.remove(old_index) {
-                player.queue.insert(new_index, track.clone());
-
-                if !player.shuffle {
-                    player.real_queue.insert(new_index, track);
-                }
-            }
-
-            state.update_queue(&player);
+            player.looping = mode;
         })
         .await;
 
     Ok(state.get().await)
 }
 
+/// Keeps the music going with similar tracks from the library once the queue runs out.
 #[tauri::command]
 #[specta::specta]
-pub async fn remove_from_queue(
+pub async fn set_autoplay(
     state: tauri::State<'_, ManagedPlayer>,
-    index: i32,
-) -> Result<AudioPlayer, String> {
+    enabled: bool,
+) -> CommandResult<AudioPlayer> {
     state
         .update(|player| {
-            player.real_queue.remove(index as usize);
-            player.queue.remove(index as usize);
-
-            state.update_queue(&player);
+            player.autoplay = enabled;
         })
         .await;
 
     Ok(state.get().await)
 }
 
+/// Repeats the part of the current track between `start` and `end` until cleared
+/// or the track changes.
 #[tauri::command]
 #[specta::specta]
-pub async fn seek(time: f64) {
-    let audio_engine = audio_player().lock().await;
-    _ = audio_engine.seek(time).await;
+pub async fn set_ab_loop(
+    state: tauri::State<'_, ManagedPlayer>,
+    start: f64,
+    end: f64,
+) -> CommandResult<AudioPlayer> {
+    let ab_loop = {
+        let player = state.player.lock().await;
+        let Some(current) = &player.currently_playing else {
+            return Err(CommandError::not_found("Nothing is playing"));
+        };
+
+        AbLoop::new(start, end, current.track.duration as f64)?
+    };
+
+    state
+        .update(|player| {
+            player.ab_loop = Some(ab_loop);
+        })
+        .await;
+
+    Ok(state.get().await)
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn shuffle(state: tauri::State<'_, ManagedPlayer>) -> Result<AudioPlayer, String> {
+pub async fn clear_ab_loop(state: tauri::State<'_, ManagedPlayer>) -> CommandResult<AudioPlayer> {
     state
         .update(|player| {
-            player.queue.clone_from(&player.real_queue);
-            if !player.shuffle {
-                player.queue.shuffle();
-            }
-            player.shuffle = !player.shuffle;
-            state.update_queue(&player);
+            player.ab_loop = None;
         })
         .await;
 
@@ -488,13 +1742,25 @@ pub async fn shuffle(state: tauri::State<'_, ManagedPlayer>) -> Result<AudioPlay
 
 pub struct ManagedPlayer {
     pub player: Arc<Mutex<AudioPlayer>>,
+    pub next_segment: Arc<Mutex<Option<FullTrack>>>, // <- A CUE track the engine plays on into
+    pub listen: Arc<Mutex<Option<Listen>>>,
+    pub playing: Arc<Notify>, // <- Wakes the progress publisher
+    pub queue_history: Arc<Mutex<QueueHistory>>,
     pub app: AppHandle,
 }
 
 impl ManagedPlayer {
     pub fn new(app: AppHandle) -> Self {
+        let player = session::load()
+            .map(AudioPlayer::from_session)
+            .unwrap_or_default();
+
         Self {
-            player: Arc::new(Mutex::new(AudioPlayer::default())),
+            player: Arc::new(Mutex::new(player)),
+            next_segment: Arc::new(Mutex::new(None)),
+            listen: Arc::new(Mutex::new(None)),
+            playing: Arc::new(Notify::new()),
+            queue_history: Arc::new(Mutex::new(QueueHistory::default())),
             app,
         }
     }
@@ -506,6 +1772,12 @@ impl ManagedPlayer {
         let mut audio_player = self.player.lock().await;
         updater(&mut *&mut audio_player);
 
+        if audio_player.state == PlayerState::Playing {
+            self.playing.notify_one();
+        }
+
+        session::save(audio_player.to_session());
+
         let new_audio_player = AudioPlayer {
             real_queue: VecDeque::new(),
             queue: VecDeque::new(),
@@ -516,10 +1788,19 @@ impl ManagedPlayer {
             state: audio_player.state.clone(),
             position: audio_player.position.clone(),
             looping: audio_player.looping.clone(),
+            sleep_timer: audio_player.sleep_timer.clone(),
+            shuffle_strategy: audio_player.shuffle_strategy,
+            autoplay: audio_player.autoplay,
+            ab_loop: audio_player.ab_loop,
+            context: audio_player.context.clone(),
+            context_tracks: Vec::new(),
         };
 
         //sending the payload without the queue data for now cause it can get big and cause slowdowns
         let _ = self.app.emit("player-changed", new_audio_player);
+
+        #[cfg(target_os = "linux")]
+        crate::mpris::player_changed();
     }
 
     pub async fn get(&self) -> AudioPlayer {
@@ -534,4 +1815,317 @@ impl ManagedPlayer {
     pub fn update_history(&self, player: &AudioPlayer) {
         let _ = self.app.emit("history-changed", player.history.clone());
     }
+
+    /// Keeps the queue as it is now, before `edit` changes it, so it can be
+    /// undone. An empty queue has nothing worth getting back.
+    pub async fn remember_queue(&self, edit: QueueEdit) {
+        let snapshot = QueueSnapshot::of(&*self.player.lock().await, edit);
+        if snapshot.real_queue.is_empty() {
+            return;
+        }
+
+        let mut history = self.queue_history.lock().await;
+        history.remember(snapshot);
+
+        let _ = self.app.emit("queue-undo-changed", history.status());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(ids: &[i64]) -> VecDeque<QueueEntry> {
+        ids.iter()
+            .map(|&id| QueueEntry::new(FullTrack::stub(id, 1)))
+            .collect()
+    }
+
+    fn saved_tracks(saved: &[session::SavedEntry]) -> Vec<i64> {
+        saved.iter().map(|e| e.track_id).collect()
+    }
+
+    fn segment(id: i64, start: f64, end: Option<f64>) -> FullTrack {
+        let mut track = FullTrack::stub(id, 1);
+        track.track.file_path = format!("/music/album.flac#{id:02}");
+        track.track.start_offset = Some(start);
+        track.track.end_offset = end;
+        track
+    }
+
+    #[test]
+    fn next_segment_continues_the_same_file() {
+        let first = segment(1, 0.0, Some(300.0));
+        let second = segment(2, 300.0, Some(600.0));
+        let third = segment(3, 600.0, None);
+
+        assert!(is_next_segment(&first, &second));
+        assert!(is_next_segment(&second, &third));
+        assert!(!is_next_segment(&first, &third));
+        assert!(!is_next_segment(&first, &first));
+        assert!(!is_next_segment(&third, &FullTrack::stub(4, 1)));
+
+        let mut elsewhere = segment(2, 300.0, None);
+        elsewhere.track.file_path = "/music/other.flac#02".to_string();
+        assert!(!is_next_segment(&first, &elsewhere));
+    }
+
+    #[test]
+    fn segment_remaining_counts_down_to_the_segment_end() {
+        let mut player = AudioPlayer {
+            currently_playing: Some(segment(2, 300.0, Some(600.0))),
+            position: 290.0,
+            ..AudioPlayer::default()
+        };
+        assert_eq!(segment_remaining(&player), Some(10_000));
+
+        player.position = 310.0;
+        assert_eq!(segment_remaining(&player), Some(0));
+
+        // The last segment and whole files end with the file
+        player.currently_playing = Some(segment(3, 600.0, None));
+        assert_eq!(segment_remaining(&player), None);
+        player.currently_playing = None;
+        assert_eq!(segment_remaining(&player), None);
+    }
+
+    #[test]
+    fn session_keeps_the_queue_by_track_and_entry() {
+        let real_queue = entries(&[1, 2, 3]);
+        let queue: VecDeque<QueueEntry> = [2, 0, 1].map(|i| real_queue[i].clone()).into();
+        let player = AudioPlayer {
+            currently_playing: Some(FullTrack::stub(7, 1)),
+            history: [FullTrack::stub(5, 1), FullTrack::stub(6, 1)].into(),
+            real_queue: real_queue.clone(),
+            queue,
+            ..AudioPlayer::default()
+        };
+
+        let session = player.to_session();
+
+        assert_eq!(session.currently_playing, Some(7));
+        assert_eq!(session.history, vec![5, 6]);
+
+        assert_eq!(saved_tracks(&session.real_queue), vec![1, 2, 3]);
+        assert_eq!(saved_tracks(&session.queue), vec![3, 1, 2]);
+        assert_eq!(session.queue[0].entry_id, real_queue[2].entry_id);
+    }
+
+    #[test]
+    fn session_keeps_only_the_latest_history() {
+        let player = AudioPlayer {
+            history: (1..=150).map(|id| FullTrack::stub(id, 1)).collect(),
+            ..AudioPlayer::default()
+        };
+
+        let session = player.to_session();
+
+        assert_eq!(session.history.len(), MAX_SAVED_HISTORY);
+        assert_eq!(session.history.first(), Some(&51));
+        assert_eq!(session.history.last(), Some(&150));
+    }
+
+    #[test]
+    fn session_round_trips_player_settings() {
+        let player = AudioPlayer {
+            shuffle: true,
+            looping: LoopType::LoopOver,
+            shuffle_strategy: ShuffleStrategy::Balanced,
+            autoplay: true,
+            context: Some(PlaybackContext::Album {
+                id: 3,
+                name: "Album".to_string(),
+            }),
+            context_tracks: vec![4, 5, 6],
+            // Saved, but only resumed along with a track
+            position: 42.0,
+            ..AudioPlayer::default()
+        };
+
+        let session = player.to_session();
+        assert_eq!(session.position, 42.0);
+
+        // Through JSON, the way it's stored on disk
+        let json = serde_json::to_string(&session).unwrap();
+        let restored = AudioPlayer::from_session(serde_json::from_str(&json).unwrap());
+
+        assert!(restored.shuffle);
+        assert_eq!(restored.looping, LoopType::LoopOver);
+        assert_eq!(restored.shuffle_strategy, ShuffleStrategy::Balanced);
+        assert!(restored.autoplay);
+        assert_eq!(restored.context, player.context);
+        assert_eq!(restored.context_tracks, vec![4, 5, 6]);
+
+        // Nothing was playing, so there's nothing to resume either
+        assert_eq!(restored.state, PlayerState::Empty);
+        assert_eq!(restored.position, 0.0);
+    }
+
+    #[test]
+    fn session_fills_in_missing_fields() {
+        let restored = AudioPlayer::from_session(serde_json::from_str("{}").unwrap());
+
+        assert!(!restored.shuffle);
+        assert_eq!(restored.looping, LoopType::Off);
+        assert!(restored.currently_playing.is_none());
+        assert!(restored.queue.is_empty());
+    }
+
+    fn queued(ids: &[i64]) -> AudioPlayer {
+        let queue = entries(ids);
+        AudioPlayer {
+            real_queue: queue.clone(),
+            queue,
+            ..AudioPlayer::default()
+        }
+    }
+
+    #[test]
+    fn undo_keeps_only_the_newest_edits() {
+        let mut history = QueueHistory::default();
+        for id in 0..MAX_QUEUE_UNDO as i64 + 5 {
+            history.push_undo(QueueSnapshot::of(&queued(&[id]), QueueEdit::Added));
+        }
+
+        assert_eq!(history.undo.len(), MAX_QUEUE_UNDO);
+        assert_eq!(history.undo[0].queue[0].track.track.id, Some(5));
+    }
+
+    #[test]
+    fn remembering_an_unchanged_queue_replaces_the_last_edit() {
+        let player = queued(&[1, 2]);
+        let mut history = QueueHistory::default();
+        history
+            .redo
+            .push(QueueSnapshot::of(&player, QueueEdit::Cleared));
+
+        history.remember(QueueSnapshot::of(&player, QueueEdit::Moved));
+        history.remember(QueueSnapshot::of(&player, QueueEdit::Removed));
+
+        assert_eq!(history.undo.len(), 1);
+        assert_eq!(history.undo[0].edit, QueueEdit::Removed);
+        assert!(history.redo.is_empty());
+
+        // Same tracks, but new entries
+        history.remember(QueueSnapshot::of(&queued(&[1, 2]), QueueEdit::Added));
+        assert_eq!(history.undo.len(), 2);
+
+        let status = history.status();
+        assert!(status.can_undo);
+        assert!(!status.can_redo);
+        assert_eq!(status.last_edit, Some(QueueEdit::Added));
+    }
+
+    #[test]
+    fn undoing_a_replaced_queue_brings_back_what_was_playing() {
+        let mut player = queued(&[2, 3]);
+        player.currently_playing = Some(FullTrack::stub(1, 1));
+        let snapshot = QueueSnapshot::of(&player, QueueEdit::Replaced);
+
+        player.currently_playing = Some(FullTrack::stub(9, 1));
+        player.real_queue = entries(&[10]);
+        player.queue = player.real_queue.clone();
+        snapshot.restore(&mut player);
+
+        let ids = |queue: &VecDeque<QueueEntry>| -> Vec<Option<i64>> {
+            queue.iter().map(|e| e.track.track.id).collect()
+        };
+        assert_eq!(ids(&player.real_queue), vec![Some(1), Some(2), Some(3)]);
+        assert_eq!(ids(&player.queue), vec![Some(1), Some(2), Some(3)]);
+        assert_eq!(player.currently_playing.unwrap().track.id, Some(9));
+    }
+
+    #[test]
+    fn undoing_a_move_leaves_what_is_playing_alone() {
+        let mut player = queued(&[2, 3]);
+        player.currently_playing = Some(FullTrack::stub(1, 1));
+        let snapshot = QueueSnapshot::of(&player, QueueEdit::Moved);
+
+        player.currently_playing = Some(FullTrack::stub(2, 1));
+        player.real_queue = entries(&[3]);
+        snapshot.restore(&mut player);
+
+        assert_eq!(player.real_queue.len(), 2);
+        assert_eq!(player.real_queue[0].track.track.id, Some(2));
+    }
+
+    fn following(ids: &[i64]) -> Vec<FullTrack> {
+        ids.iter().map(|&id| FullTrack::stub(id, 1)).collect()
+    }
+
+    fn queue_ids(queue: &VecDeque<QueueEntry>) -> Vec<i64> {
+        queue.iter().filter_map(|e| e.track.track.id).collect()
+    }
+
+    /// Playing 2 out of 1 to 4, with 8 queued by hand.
+    fn in_context() -> AudioPlayer {
+        AudioPlayer {
+            currently_playing: Some(FullTrack::stub(2, 1)),
+            context_tracks: vec![1, 2, 3, 4],
+            ..queued(&[3, 4, 8])
+        }
+    }
+
+    #[test]
+    fn following_an_unchanged_context_does_nothing() {
+        let mut player = in_context();
+        let before = player.queue.clone();
+
+        assert!(!player.follow_context(&following(&[4, 3, 2, 1])));
+        assert_eq!(queue_ids(&player.queue), queue_ids(&before));
+        assert_eq!(player.queue[0].entry_id, before[0].entry_id);
+    }
+
+    #[test]
+    fn follows_tracks_the_context_lost_and_gained() {
+        let mut player = in_context();
+
+        assert!(player.follow_context(&following(&[1, 2, 5, 3, 6])));
+        assert_eq!(queue_ids(&player.real_queue), vec![5, 3, 6, 8]);
+        assert_eq!(queue_ids(&player.queue), vec![5, 3, 6, 8]);
+        assert_eq!(player.context_tracks, vec![1, 2, 5, 3, 6]);
+    }
+
+    #[test]
+    fn tracks_added_before_the_current_one_are_not_queued() {
+        let mut player = in_context();
+
+        assert!(player.follow_context(&following(&[0, 1, 2, 3, 4])));
+        assert_eq!(queue_ids(&player.queue), vec![3, 4, 8]);
+        assert_eq!(player.context_tracks, vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn shuffled_queue_gets_gained_tracks_anywhere() {
+        let mut player = in_context();
+        player.shuffle = true;
+        player.queue = [2, 0, 1].map(|i| player.real_queue[i].clone()).into();
+
+        assert!(player.follow_context(&following(&[0, 1, 2, 4, 7])));
+        assert_eq!(queue_ids(&player.real_queue), vec![0, 4, 7, 8]);
+
+        let mut shuffled = queue_ids(&player.queue);
+        shuffled.sort();
+        assert_eq!(shuffled, vec![0, 4, 7, 8]);
+    }
+
+    #[test]
+    fn loop_region_stays_within_the_track() {
+        assert_eq!(
+            AbLoop::new(10.0, 20.5, 180.0).ok(),
+            Some(AbLoop {
+                start: 10.0,
+                end: 20.5
+            })
+        );
+        assert!(AbLoop::new(0.0, 180.0, 180.0).is_ok());
+
+        assert!(AbLoop::new(-1.0, 20.0, 180.0).is_err());
+        assert!(AbLoop::new(20.0, 20.0, 180.0).is_err());
+        assert!(AbLoop::new(30.0, 20.0, 180.0).is_err());
+        assert!(AbLoop::new(170.0, 190.0, 180.0).is_err());
+
+        // Without a known length only the order is checked
+        assert!(AbLoop::new(170.0, 1000.0, 0.0).is_ok());
+    }
 }
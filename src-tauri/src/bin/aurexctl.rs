@@ -0,0 +1,175 @@
+//! Command-line remote for a running Aurex, meant for keybindings and status bars.
+
+use std::{env, process::ExitCode};
+
+use aurex_desktop_lib::constants;
+use serde_json::Value;
+
+const USAGE: &str = "Usage: aurexctl <command>
+
+Commands:
+  play                 Resume playback
+  pause                Pause playback
+  toggle               Play or pause
+  next                 Skip to the next track
+  prev                 Go back to the previous track
+  seek <[h:]m:ss|s>    Jump to a position in the current track
+  queue add <path>     Add a library track to the end of the queue
+  now-playing [--json] Show the current track
+  search <term>        Search the library
+  rescan               Re-index the library folders";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let request = match to_request(&args) {
+        Ok(request) => request,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let reply = match send(&request) {
+        Ok(reply) => reply,
+        Err(e) => {
+            eprintln!("Could not reach Aurex, is it running? ({})", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let payload = match reply.split_once(' ') {
+        Some(("ok", payload)) => Some(payload),
+        None if reply == "ok" => None,
+        _ => {
+            eprintln!("{}", reply.strip_prefix("error ").unwrap_or(&reply));
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if let Some(payload) = payload {
+        print_payload(&args, payload);
+    }
+
+    ExitCode::SUCCESS
+}
+
+/// Turns the command line into the line sent over the socket.
+fn to_request(args: &[String]) -> Result<String, String> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match args.as_slice() {
+        [cmd @ ("play" | "pause" | "toggle" | "next" | "prev" | "rescan")] => Ok(cmd.to_string()),
+        ["seek", position] => parse_position(position).map(|secs| format!("seek {}", secs)),
+        ["queue", "add", path] => {
+            // The library stores absolute paths
+            let path = std::fs::canonicalize(path).map_err(|e| format!("{}: {}", path, e))?;
+            Ok(format!("queue-add {}", path.display()))
+        }
+        ["now-playing"] | ["now-playing", "--json"] => Ok("now-playing".to_string()),
+        ["search", term @ ..] if !term.is_empty() => Ok(format!("search {}", term.join(" "))),
+        [] => Err("Missing command".to_string()),
+        _ => Err(format!("Invalid command: {}", args.join(" "))),
+    }
+}
+
+/// Accepts `83`, `1:23` and `1:02:03`.
+fn parse_position(position: &str) -> Result<f64, String> {
+    let invalid = || format!("Invalid position: {}", position);
+
+    position.split(':').try_fold(0.0, |total, part| {
+        let value = part.parse::<f64>().map_err(|_| invalid())?;
+        if value < 0.0 {
+            return Err(invalid());
+        }
+        Ok(total * 60.0 + value)
+    })
+}
+
+#[cfg(unix)]
+fn send(request: &str) -> std::io::Result<String> {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        os::unix::net::UnixStream,
+    };
+
+    let mut stream = UnixStream::connect(constants::ipc_socket())?;
+    stream.write_all(request.as_bytes())?;
+    stream.write_all(b"\n")?;
+
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    Ok(reply.trim_end().to_string())
+}
+
+#[cfg(not(unix))]
+fn send(_request: &str) -> std::io::Result<String> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "aurexctl is only available on Unix",
+    ))
+}
+
+fn print_payload(args: &[String], payload: &str) {
+    if args.iter().any(|a| a == "--json") {
+        println!("{}", payload);
+        return;
+    }
+
+    let Ok(value) = serde_json::from_str::<Value>(payload) else {
+        println!("{}", payload);
+        return;
+    };
+
+    match args.first().map(String::as_str) {
+        Some("now-playing") => {
+            let track = &value["track"];
+            if track.is_null() {
+                return;
+            }
+            println!(
+                "{} - {}",
+                track["artist_name"].as_str().unwrap_or_default(),
+                track["track"]["title"].as_str().unwrap_or_default()
+            );
+        }
+        Some("search") => {
+            for result in value["tracks"].as_array().into_iter().flatten() {
+                let track = &result["track"];
+                println!(
+                    "{}\t{} - {}",
+                    track["track"]["file_path"].as_str().unwrap_or_default(),
+                    track["artist_name"].as_str().unwrap_or_default(),
+                    track["track"]["title"].as_str().unwrap_or_default()
+                );
+            }
+        }
+        _ => println!("{}", payload),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_seconds() {
+        assert_eq!(parse_position("90"), Ok(90.0));
+        assert_eq!(parse_position("12.5"), Ok(12.5));
+    }
+
+    #[test]
+    fn parses_minutes_and_hours() {
+        assert_eq!(parse_position("1:30"), Ok(90.0));
+        assert_eq!(parse_position("1:30.5"), Ok(90.5));
+        assert_eq!(parse_position("1:02:03"), Ok(3723.0));
+    }
+
+    #[test]
+    fn rejects_anything_else() {
+        assert!(parse_position("").is_err());
+        assert!(parse_position("1:").is_err());
+        assert!(parse_position("a:30").is_err());
+        assert!(parse_position("-1:00").is_err());
+    }
+}
@@ -0,0 +1,182 @@
+use tauri::Manager;
+
+use crate::{
+    audio_player::{current_progress, load, play, seek, ManagedPlayer},
+    error::{CommandError, CommandResult},
+    library_service::library_service,
+    models::{Bookmark, FullTrack},
+    settings::ManagedSettings,
+};
+
+/// Positions this close to either end of a track aren't worth resuming from.
+const RESUME_MARGIN: f64 = 10.0;
+
+/// Audiobooks, mixes and lectures: anything flagged as such, or simply long enough.
+async fn is_long_form(state: &ManagedPlayer, track: &FullTrack) -> bool {
+    let min_length = state
+        .app
+        .state::<ManagedSettings>()
+        .get()
+        .await
+        .long_form_min_length;
+    if min_length > 0.0 && track.track.duration as f64 >= min_length {
+        return true;
+    }
+
+    match (library_service().lock(), track.track.id) {
+        (Ok(library), Some(id)) => library.is_long_form(id).unwrap_or(false),
+        _ => false,
+    }
+}
+
+/// Stores where a long-form track was left, or forgets it when there's
+/// nothing meaningful to come back to.
+pub async fn remember_position(state: &ManagedPlayer, track: &FullTrack, position: f64) {
+    let Some(id) = track.track.id else {
+        return;
+    };
+
+    if !is_long_form(state, track).await {
+        return;
+    }
+
+    let duration = track.track.duration as f64;
+    if let Ok(library) = library_service().lock() {
+        if position > RESUME_MARGIN && position < duration - RESUME_MARGIN {
+            _ = library.save_track_position(id, position);
+        } else {
+            _ = library.delete_track_position(id);
+        }
+    }
+}
+
+/// A track that played to the end starts from the top next time.
+pub fn forget_position(track: &FullTrack) {
+    if let (Ok(library), Some(id)) = (library_service().lock(), track.track.id) {
+        _ = library.delete_track_position(id);
+    }
+}
+
+pub async fn resume_position(state: &ManagedPlayer, track: &FullTrack) -> Option<f64> {
+    let id = track.track.id?;
+
+    if !is_long_form(state, track).await {
+        return None;
+    }
+
+    library_service()
+        .lock()
+        .ok()?
+        .get_track_position(id)
+        .ok()
+        .flatten()
+}
+
+// <------------Commands------------>
+#[tauri::command]
+#[specta::specta]
+pub async fn set_track_long_form(track_id: i64, long_form: bool) -> CommandResult<()> {
+    Ok(library_service()
+        .lock()?
+        .set_track_long_form(track_id, long_form)?)
+}
+
+/// Flags every track under `path` as long-form, including ones added later.
+#[tauri::command]
+#[specta::specta]
+pub async fn set_dir_long_form(path: String, long_form: bool) -> CommandResult<()> {
+    Ok(library_service()
+        .lock()?
+        .set_dir_long_form(&path, long_form)?)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_long_form_dirs() -> CommandResult<Vec<String>> {
+    Ok(library_service().lock()?.get_long_form_dirs()?)
+}
+
+/// Marks the current position of the playing track.
+#[tauri::command]
+#[specta::specta]
+pub async fn add_bookmark(
+    state: tauri::State<'_, ManagedPlayer>,
+    name: Option<String>,
+) -> CommandResult<Bookmark> {
+    let Some(track_id) = state
+        .player
+        .lock()
+        .await
+        .currently_playing
+        .as_ref()
+        .and_then(|t| t.track.id)
+    else {
+        return Err(CommandError::not_found("Nothing is playing"));
+    };
+
+    let Some(position) = current_progress(&state).await else {
+        return Err(CommandError::Engine(
+            "Could not read the playback position".into(),
+        ));
+    };
+
+    let name = name.unwrap_or_else(|| {
+        let secs = position as i64;
+        format!("{}:{:02}", secs / 60, secs % 60)
+    });
+
+    let library = library_service().lock()?;
+    let id = library.add_bookmark(track_id, &name, position)?;
+
+    library
+        .get_bookmark(id)?
+        .ok_or_else(|| CommandError::not_found(format!("Bookmark {id}")))
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn list_bookmarks(track_id: i64) -> CommandResult<Vec<Bookmark>> {
+    Ok(library_service().lock()?.get_bookmarks(track_id)?)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn delete_bookmark(bookmark_id: i64) -> CommandResult<()> {
+    Ok(library_service().lock()?.delete_bookmark(bookmark_id)?)
+}
+
+/// Seeks to a bookmark, switching tracks first if it belongs to another one.
+#[tauri::command]
+#[specta::specta]
+pub async fn jump_to_bookmark(
+    state: tauri::State<'_, ManagedPlayer>,
+    bookmark_id: i64,
+) -> CommandResult<()> {
+    let (bookmark, track) = {
+        let library = library_service().lock()?;
+        let bookmark = library
+            .get_bookmark(bookmark_id)?
+            .ok_or_else(|| CommandError::not_found(format!("Bookmark {bookmark_id}")))?;
+        let track = library
+            .get_full_track_by_id(bookmark.track_id)?
+            .ok_or_else(|| CommandError::not_found(format!("Track {}", bookmark.track_id)))?;
+        (bookmark, track)
+    };
+
+    let current = state
+        .player
+        .lock()
+        .await
+        .currently_playing
+        .as_ref()
+        .and_then(|t| t.track.id);
+
+    if current != track.track.id {
+        load(state.clone(), track).await?;
+        play(state.clone()).await?;
+    }
+
+    seek(state, bookmark.position).await?;
+
+    Ok(())
+}
@@ -22,3 +22,22 @@ pub fn dir_file() -> PathBuf {
 pub fn cover_cache() -> PathBuf {
     app_cache().join("covers")
 }
+
+pub fn session_file() -> PathBuf {
+    app_data().join("session.json")
+}
+
+pub fn settings_file() -> PathBuf {
+    app_data().join("settings.json")
+}
+
+/// Where the running app listens for `aurexctl`.
+pub fn ipc_socket() -> PathBuf {
+    dirs::runtime_dir()
+        .unwrap_or_else(app_data)
+        .join("aurex.sock")
+}
+
+pub fn scrobbler_log() -> PathBuf {
+    app_data().join(".scrobbler.log")
+}
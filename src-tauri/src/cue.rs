@@ -0,0 +1,471 @@
+//! CUE sheets, which split a single-file rip into its tracks. Each track becomes
+//! its own row in the library, pointing at a slice of the shared audio file.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::models::FileMetadata;
+
+/// CUE timestamps count frames, 75 of them to the second.
+const FRAMES_PER_SECOND: f64 = 75.0;
+
+#[derive(Debug, Default)]
+pub struct CueSheet {
+    pub performer: Option<String>,
+    pub title: Option<String>,
+    pub genre: Option<String>,
+    pub date: Option<String>,
+    pub files: Vec<CueFile>,
+}
+
+#[derive(Debug)]
+pub struct CueFile {
+    pub name: String,
+    pub tracks: Vec<CueTrack>,
+}
+
+#[derive(Debug)]
+pub struct CueTrack {
+    pub number: i64,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub songwriter: Option<String>,
+    pub isrc: Option<String>,
+    /// Seconds from the start of the file, taken from `INDEX 01`.
+    pub start: Option<f64>,
+}
+
+/// The sheets found in each directory during one scan, so every audio file
+/// next to them doesn't parse them all over again.
+#[derive(Default)]
+pub struct SheetCache {
+    dirs: Mutex<HashMap<PathBuf, Arc<Vec<CueSheet>>>>,
+}
+
+impl SheetCache {
+    fn sheets_in(&self, dir: &Path) -> Arc<Vec<CueSheet>> {
+        if let Some(sheets) = self.dirs.lock().ok().and_then(|d| d.get(dir).cloned()) {
+            return sheets;
+        }
+
+        // Parsed without holding the lock, the scan runs in parallel
+        let sheets = Arc::new(read_sheets(dir));
+
+        match self.dirs.lock() {
+            Ok(mut dirs) => dirs.entry(dir.to_path_buf()).or_insert(sheets).clone(),
+            Err(_) => sheets,
+        }
+    }
+}
+
+/// Virtual tracks keep the file they're cut from in their path so it stays unique.
+pub fn segment_path(audio: &Path, number: i64) -> PathBuf {
+    let mut path = audio.as_os_str().to_owned();
+    path.push(format!("#{number:02}"));
+    PathBuf::from(path)
+}
+
+pub fn parse(contents: &str) -> CueSheet {
+    let mut sheet = CueSheet::default();
+
+    for line in contents.trim_start_matches('\u{feff}').lines() {
+        let line = line.trim();
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+
+        let track = sheet
+            .files
+            .last_mut()
+            .and_then(|file| file.tracks.last_mut());
+
+        match (command.to_ascii_uppercase().as_str(), track) {
+            ("FILE", _) => sheet.files.push(CueFile {
+                name: file_name(rest),
+                tracks: Vec::new(),
+            }),
+            ("TRACK", _) => {
+                let number = rest
+                    .split_whitespace()
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or(0);
+
+                if let Some(file) = sheet.files.last_mut() {
+                    file.tracks.push(CueTrack {
+                        number,
+                        title: None,
+                        performer: None,
+                        songwriter: None,
+                        isrc: None,
+                        start: None,
+                    });
+                }
+            }
+            ("INDEX", Some(track)) => {
+                let mut parts = rest.split_whitespace();
+                if parts.next().and_then(|n| n.parse::<u32>().ok()) == Some(1) {
+                    track.start = parts.next().and_then(parse_time);
+                }
+            }
+            ("TITLE", Some(track)) => track.title = Some(unquote(rest)),
+            ("PERFORMER", Some(track)) => track.performer = Some(unquote(rest)),
+            ("SONGWRITER", Some(track)) => track.songwriter = Some(unquote(rest)),
+            ("ISRC", Some(track)) => track.isrc = Some(unquote(rest)),
+            ("TITLE", None) => sheet.title = Some(unquote(rest)),
+            ("PERFORMER", None) => sheet.performer = Some(unquote(rest)),
+            ("REM", _) => {
+                let (key, value) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                match key.to_ascii_uppercase().as_str() {
+                    "GENRE" => sheet.genre = Some(unquote(value)),
+                    "DATE" => sheet.date = Some(unquote(value)),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    sheet
+}
+
+/// Looks for a CUE sheet next to the file that describes it and splits the
+/// metadata into one entry per track. Files without one come back as is.
+pub fn split(meta: FileMetadata, cache: &SheetCache) -> Vec<FileMetadata> {
+    let Some(dir) = meta.path.parent() else {
+        return vec![meta];
+    };
+    let sheets = cache.sheets_in(dir);
+    let Some((sheet, file)) = find_sheet(&meta.path, &sheets) else {
+        return vec![meta];
+    };
+
+    let year = sheet
+        .date
+        .as_deref()
+        .and_then(|d| d.get(..4))
+        .and_then(|y| y.parse().ok())
+        .or(meta.year);
+
+    // A sheet with a single track for the file has nothing to cut
+    let tracks: Vec<&CueTrack> = file.tracks.iter().filter(|t| t.start.is_some()).collect();
+    if tracks.len() < 2 {
+        return vec![meta];
+    }
+
+    tracks
+        .iter()
+        .enumerate()
+        .map(|(i, track)| {
+            let start = track.start.unwrap_or(0.0);
+            let end = tracks.get(i + 1).and_then(|next| next.start);
+            let length = match end {
+                Some(end) => end - start,
+                None => meta.duration.unwrap_or(0) as f64 - start,
+            };
+
+            FileMetadata {
+                path: segment_path(&meta.path, track.number),
+                title: track
+                    .title
+                    .clone()
+                    .or_else(|| Some(format!("Track {:02}", track.number))),
+                artist: track
+                    .performer
+                    .clone()
+                    .or_else(|| sheet.performer.clone())
+                    .or_else(|| meta.artist.clone()),
+                album_artist: sheet
+                    .performer
+                    .clone()
+                    .or_else(|| meta.album_artist.clone()),
+                album: sheet.title.clone().or_else(|| meta.album.clone()),
+                genre: sheet.genre.clone().or_else(|| meta.genre.clone()),
+                duration: Some(length.max(0.0).round() as i64),
+                year,
+                track_num: Some(track.number),
+                disc_num: meta.disc_num,
+                bpm: None,
+                initial_key: None,
+                isrc: track.isrc.clone(),
+                lyrics: None,
+                composer: track.songwriter.clone(),
+                // The cover only has to be moved into place once per album
+                cover_path: if i == 0 {
+                    meta.cover_path.clone()
+                } else {
+                    None
+                },
+                // Gains measured over the whole file don't describe a single track
+                track_gain: None,
+                track_peak: None,
+                album_gain: meta.album_gain.or(meta.track_gain),
+                album_peak: meta.album_peak.or(meta.track_peak),
+                musicbrainz_recording_id: None,
+                start_offset: Some(start),
+                end_offset: end,
+            }
+        })
+        .collect()
+}
+
+fn read_sheets(dir: &Path) -> Vec<CueSheet> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|path| {
+            path.extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| e.eq_ignore_ascii_case("cue"))
+        })
+        .filter_map(|path| fs::read(path).ok())
+        .map(|bytes| parse(&String::from_utf8_lossy(&bytes)))
+        .collect()
+}
+
+/// The sheet and `FILE` entry describing `audio`. Sheets often still name the
+/// `.wav` the rip was encoded from, so a file with the same stem also counts.
+fn find_sheet<'a>(audio: &Path, sheets: &'a [CueSheet]) -> Option<(&'a CueSheet, &'a CueFile)> {
+    let name = audio.file_name()?.to_str()?;
+    let stem = audio.file_stem()?.to_str()?;
+
+    let find = |matches: &dyn Fn(&CueFile) -> bool| {
+        sheets.iter().find_map(|sheet| {
+            sheet
+                .files
+                .iter()
+                .find(|f| matches(f) && f.tracks.iter().any(|t| t.start.is_some()))
+                .map(|file| (sheet, file))
+        })
+    };
+
+    find(&|f| f.name == name).or_else(|| {
+        find(&|f| Path::new(&f.name).file_stem().and_then(|s| s.to_str()) == Some(stem))
+    })
+}
+
+/// `FILE "name.flac" WAVE`, the name may be a path relative to the sheet.
+fn file_name(rest: &str) -> String {
+    let name = match rest.strip_prefix('"') {
+        Some(quoted) => quoted.split('"').next().unwrap_or(quoted).to_string(),
+        None => rest.split_whitespace().next().unwrap_or(rest).to_string(),
+    };
+
+    name.rsplit(['/', '\\']).next().unwrap_or(&name).to_string()
+}
+
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value)
+        .to_string()
+}
+
+/// `mm:ss:ff`, minutes may go past 59.
+fn parse_time(time: &str) -> Option<f64> {
+    let mut parts = time.split(':').map(|p| p.parse::<u32>().ok());
+    let minutes = parts.next()??;
+    let seconds = parts.next()??;
+    let frames = parts.next()??;
+
+    Some(minutes as f64 * 60.0 + seconds as f64 + frames as f64 / FRAMES_PER_SECOND)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHEET: &str = "\u{feff}REM GENRE \"Jazz\"
+REM DATE 1959
+PERFORMER \"Miles Davis\"
+TITLE \"Kind of Blue\"
+FILE \"C:\\rips\\Kind of Blue.wav\" WAVE
+  TRACK 01 AUDIO
+    TITLE \"So What\"
+    ISRC USSM15900113
+    INDEX 00 00:00:00
+    INDEX 01 00:00:32
+  TRACK 02 AUDIO
+    TITLE \"Freddie Freeloader\"
+    PERFORMER \"Miles Davis Sextet\"
+    SONGWRITER \"Miles Davis\"
+    INDEX 01 09:22:15
+  track 03 audio
+    index 01 18:59:00
+";
+
+    fn metadata(path: PathBuf) -> FileMetadata {
+        FileMetadata {
+            path,
+            title: Some("Kind of Blue".to_string()),
+            artist: Some("Someone Else".to_string()),
+            album_artist: None,
+            album: None,
+            genre: None,
+            duration: Some(1500),
+            year: None,
+            track_num: None,
+            disc_num: Some(1),
+            bpm: None,
+            initial_key: None,
+            isrc: None,
+            lyrics: None,
+            composer: None,
+            cover_path: Some(PathBuf::from("cover.jpg")),
+            track_gain: Some(-7.5),
+            track_peak: Some(0.98),
+            album_gain: None,
+            album_peak: None,
+            musicbrainz_recording_id: None,
+            start_offset: None,
+            end_offset: None,
+        }
+    }
+
+    #[test]
+    fn parses_times_in_frames() {
+        assert_eq!(parse_time("00:00:00"), Some(0.0));
+        assert_eq!(parse_time("01:02:15"), Some(62.2));
+        assert_eq!(parse_time("75:00:00"), Some(4500.0));
+        assert_eq!(parse_time("01:02"), None);
+        assert_eq!(parse_time("aa:00:00"), None);
+    }
+
+    #[test]
+    fn strips_quotes_and_directories_from_file_names() {
+        assert_eq!(file_name("\"Album.flac\" WAVE"), "Album.flac");
+        assert_eq!(file_name("\"rips/My Album.flac\" WAVE"), "My Album.flac");
+        assert_eq!(file_name("Album.wav WAVE"), "Album.wav");
+        assert_eq!(unquote(" \"So What\" "), "So What");
+        assert_eq!(unquote("So What"), "So What");
+    }
+
+    #[test]
+    fn parses_sheet_and_track_fields() {
+        let sheet = parse(SHEET);
+
+        assert_eq!(sheet.performer.as_deref(), Some("Miles Davis"));
+        assert_eq!(sheet.title.as_deref(), Some("Kind of Blue"));
+        assert_eq!(sheet.genre.as_deref(), Some("Jazz"));
+        assert_eq!(sheet.date.as_deref(), Some("1959"));
+        assert_eq!(sheet.files.len(), 1);
+
+        let file = &sheet.files[0];
+        assert_eq!(file.name, "Kind of Blue.wav");
+        assert_eq!(file.tracks.len(), 3);
+
+        let first = &file.tracks[0];
+        assert_eq!(first.number, 1);
+        assert_eq!(first.title.as_deref(), Some("So What"));
+        assert_eq!(first.performer, None);
+        assert_eq!(first.isrc.as_deref(), Some("USSM15900113"));
+        // INDEX 00 is the pregap, the track starts at INDEX 01
+        assert_eq!(first.start, Some(32.0 / FRAMES_PER_SECOND));
+
+        let second = &file.tracks[1];
+        assert_eq!(second.performer.as_deref(), Some("Miles Davis Sextet"));
+        assert_eq!(second.songwriter.as_deref(), Some("Miles Davis"));
+        assert_eq!(
+            second.start,
+            Some(9.0 * 60.0 + 22.0 + 15.0 / FRAMES_PER_SECOND)
+        );
+
+        assert_eq!(file.tracks[2].number, 3);
+        assert_eq!(file.tracks[2].start, Some(18.0 * 60.0 + 59.0));
+    }
+
+    #[test]
+    fn ignores_tracks_outside_a_file() {
+        let sheet = parse("TRACK 01 AUDIO\nTITLE \"Album\"\nINDEX 01 00:00:00\n");
+
+        assert!(sheet.files.is_empty());
+        assert_eq!(sheet.title.as_deref(), Some("Album"));
+    }
+
+    #[test]
+    fn finds_sheet_by_name_then_by_stem() {
+        let sheets = vec![parse(SHEET)];
+
+        let (_, file) = find_sheet(Path::new("/music/Kind of Blue.flac"), &sheets).unwrap();
+        assert_eq!(file.name, "Kind of Blue.wav");
+        assert!(find_sheet(Path::new("/music/Kind of Blue.wav"), &sheets).is_some());
+        assert!(find_sheet(Path::new("/music/Sketches of Spain.flac"), &sheets).is_none());
+
+        // A FILE entry without any usable INDEX doesn't describe anything
+        let empty = vec![parse("FILE \"Kind of Blue.flac\" WAVE\nTRACK 01 AUDIO\n")];
+        assert!(find_sheet(Path::new("/music/Kind of Blue.flac"), &empty).is_none());
+    }
+
+    #[test]
+    fn splits_file_into_segments() {
+        let dir = std::env::temp_dir().join(format!("aurex-cue-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("Kind of Blue.cue"), SHEET).unwrap();
+
+        let audio = dir.join("Kind of Blue.flac");
+        let tracks = split(metadata(audio.clone()), &SheetCache::default());
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(tracks.len(), 3);
+        assert_eq!(tracks[0].path, segment_path(&audio, 1));
+        assert!(tracks[1]
+            .path
+            .to_string_lossy()
+            .ends_with("Kind of Blue.flac#02"));
+
+        let first = &tracks[0];
+        assert_eq!(first.title.as_deref(), Some("So What"));
+        assert_eq!(first.artist.as_deref(), Some("Miles Davis"));
+        assert_eq!(first.album_artist.as_deref(), Some("Miles Davis"));
+        assert_eq!(first.album.as_deref(), Some("Kind of Blue"));
+        assert_eq!(first.genre.as_deref(), Some("Jazz"));
+        assert_eq!(first.year, Some(1959));
+        assert_eq!(first.track_num, Some(1));
+        assert_eq!(first.end_offset, tracks[1].start_offset);
+        assert!(first.cover_path.is_some());
+        assert_eq!(first.track_gain, None);
+        assert_eq!(first.album_gain, Some(-7.5));
+
+        assert_eq!(tracks[1].artist.as_deref(), Some("Miles Davis Sextet"));
+        assert!(tracks[1].cover_path.is_none());
+
+        // Untitled tracks get a number, the last one runs to the end of the file
+        let last = &tracks[2];
+        assert_eq!(last.title.as_deref(), Some("Track 03"));
+        assert_eq!(last.end_offset, None);
+        assert_eq!(last.duration, Some(1500 - (18 * 60 + 59)));
+    }
+
+    #[test]
+    fn leaves_single_track_files_whole() {
+        let dir = std::env::temp_dir().join(format!("aurex-cue-single-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let sheet = "TITLE \"Single\"\nFILE \"Single.flac\" WAVE\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n";
+        fs::write(dir.join("Single.cue"), sheet).unwrap();
+
+        let audio = dir.join("Single.flac");
+        let tracks = split(metadata(audio.clone()), &SheetCache::default());
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].path, audio);
+        assert_eq!(tracks[0].start_offset, None);
+    }
+
+    #[test]
+    fn leaves_files_without_sheet_alone() {
+        let audio = PathBuf::from("/nonexistent/aurex/Album.flac");
+        let tracks = split(metadata(audio.clone()), &SheetCache::default());
+
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].path, audio);
+        assert_eq!(tracks[0].start_offset, None);
+    }
+}
@@ -1,3 +1,7 @@
+use std::sync::PoisonError;
+
+use serde::Serialize;
+use specta::Type;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -16,3 +20,85 @@ pub enum LibraryError {
 }
 
 pub type Result<T> = std::result::Result<T, LibraryError>;
+
+/// What commands hand back to the frontend when they fail. Serialized as
+/// `{ kind, message }` so the UI can tell an empty library from a broken one.
+#[derive(Debug, Error, Serialize, Type)]
+#[serde(tag = "kind", content = "message")]
+pub enum CommandError {
+    #[error("Database error: {0}")]
+    Database(String),
+
+    #[error("IO error: {0}")]
+    Io(String),
+
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("Audio engine error: {0}")]
+    Engine(String),
+
+    #[error("Invalid index: {0}")]
+    InvalidIndex(usize),
+
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+
+    #[error("Network error: {0}")]
+    Network(String),
+
+    #[error("A lock was poisoned by a panic")]
+    Poisoned,
+}
+
+impl CommandError {
+    /// The engine's errors carry no more than their debug output.
+    pub fn engine(e: impl std::fmt::Debug) -> Self {
+        Self::Engine(format!("{e:?}"))
+    }
+
+    pub fn not_found(what: impl Into<String>) -> Self {
+        Self::NotFound(what.into())
+    }
+
+    pub fn invalid(why: impl Into<String>) -> Self {
+        Self::InvalidArgument(why.into())
+    }
+}
+
+impl From<LibraryError> for CommandError {
+    fn from(e: LibraryError) -> Self {
+        match e {
+            LibraryError::Database(e) => Self::Database(e.to_string()),
+            LibraryError::Io(e) => Self::Io(e.to_string()),
+            LibraryError::NoAppDir => Self::Io(e.to_string()),
+            LibraryError::NotFound => Self::NotFound("Record".into()),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for CommandError {
+    fn from(e: rusqlite::Error) -> Self {
+        Self::Database(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for CommandError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e.to_string())
+    }
+}
+
+impl<T> From<PoisonError<T>> for CommandError {
+    fn from(_: PoisonError<T>) -> Self {
+        Self::Poisoned
+    }
+}
+
+impl From<reqwest::Error> for CommandError {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Network(e.to_string())
+    }
+}
+
+pub type CommandResult<T> = std::result::Result<T, CommandError>;
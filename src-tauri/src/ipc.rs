@@ -0,0 +1,162 @@
+//! Unix socket listener for `aurexctl`. Every connection sends one command
+//! line and gets one reply line back: `ok`, `ok <json>` or `error <message>`.
+
+use std::{fs, os::unix::fs::PermissionsExt};
+
+use serde_json::json;
+use tauri::{AppHandle, Manager};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+};
+
+use crate::{
+    audio_player::{self, ManagedPlayer},
+    constants::ipc_socket,
+    error::{CommandError, CommandResult},
+    library_service::library_service,
+    media_lib_cmd, metadata,
+};
+
+pub fn start_ipc(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let path = ipc_socket();
+
+        // A socket nobody answers on is left over from a run that didn't shut
+        // down cleanly, one that answers belongs to another running instance
+        if path.exists() {
+            if UnixStream::connect(&path).await.is_ok() {
+                eprintln!("IPC socket {} is already in use", path.display());
+                return;
+            }
+            _ = fs::remove_file(&path);
+        }
+
+        let listener = match UnixListener::bind(&path) {
+            Ok(listener) => listener,
+            Err(e) => {
+                eprintln!("Failed to start IPC listener: {}", e);
+                return;
+            }
+        };
+
+        // Anyone who can connect can drive the player, so it's owner-only
+        if let Err(e) = fs::set_permissions(&path, fs::Permissions::from_mode(0o600)) {
+            eprintln!("Failed to restrict the IPC socket: {}", e);
+            _ = fs::remove_file(&path);
+            return;
+        }
+
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let app_handle = app_handle.clone();
+                    tauri::async_runtime::spawn(async move {
+                        handle_connection(app_handle, stream).await;
+                    });
+                }
+                Err(e) => eprintln!("IPC connection failed: {}", e),
+            }
+        }
+    });
+}
+
+async fn handle_connection(app_handle: AppHandle, stream: UnixStream) {
+    let (reader, mut writer) = stream.into_split();
+    let mut line = String::new();
+
+    if BufReader::new(reader).read_line(&mut line).await.is_err() {
+        return;
+    }
+
+    let reply = match handle_command(&app_handle, line.trim()).await {
+        Ok(None) => "ok".to_string(),
+        Ok(Some(payload)) => format!("ok {}", payload),
+        Err(e) => format!("error {}", e),
+    };
+
+    _ = writer.write_all(reply.as_bytes()).await;
+    _ = writer.write_all(b"\n").await;
+}
+
+async fn handle_command(app_handle: &AppHandle, line: &str) -> CommandResult<Option<String>> {
+    let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+    let args = args.trim();
+
+    match command {
+        "play" => {
+            audio_player::play(app_handle.state()).await?;
+        }
+        "pause" => {
+            audio_player::pause(app_handle.state()).await?;
+        }
+        "toggle" => {
+            let playing = app_handle.state::<ManagedPlayer>().get().await.state
+                == audio_player::PlayerState::Playing;
+            if playing {
+                audio_player::pause(app_handle.state()).await?;
+            } else {
+                audio_player::play(app_handle.state()).await?;
+            }
+        }
+        "next" => {
+            audio_player::next(app_handle.state()).await?;
+        }
+        "prev" => {
+            audio_player::previous(app_handle.state()).await?;
+        }
+        "seek" => {
+            let time = args
+                .parse::<f64>()
+                .map_err(|_| CommandError::invalid(format!("Invalid position: {}", args)))?;
+            audio_player::seek(app_handle.state(), time).await?;
+        }
+        "queue-add" => {
+            let track = {
+                let library = library_service().lock()?;
+                let id = library
+                    .get_track_id_by_path(args)
+                    .ok_or_else(|| CommandError::not_found(args))?;
+                library
+                    .get_full_track_by_id(id)?
+                    .ok_or_else(|| CommandError::not_found(args))?
+            };
+            audio_player::add_to_queue(app_handle.state(), track).await?;
+        }
+        "now-playing" => {
+            let player = app_handle.state::<ManagedPlayer>().get().await;
+            let position = audio_player::current_progress(&app_handle.state::<ManagedPlayer>())
+                .await
+                .unwrap_or(player.position);
+
+            let payload = json!({
+                "state": player.state,
+                "track": player.currently_playing,
+                "position": position,
+            });
+            return Ok(Some(payload.to_string()));
+        }
+        "search" => {
+            let results = media_lib_cmd::search(args.to_string()).await?;
+            return serde_json::to_string(&results)
+                .map(Some)
+                .map_err(|e| CommandError::Io(e.to_string()));
+        }
+        "rescan" => {
+            let app_handle = app_handle.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = metadata::index(app_handle).await {
+                    eprintln!("Rescan failed: {}", e);
+                }
+            });
+        }
+        _ => {
+            return Err(CommandError::invalid(format!(
+                "Unknown command: {}",
+                command
+            )))
+        }
+    }
+
+    Ok(None)
+}
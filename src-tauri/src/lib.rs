@@ -1,21 +1,41 @@
 mod app_state;
 mod audio_player;
-mod constants;
+mod bookmarks;
+pub mod constants;
+mod cue;
 mod error;
+#[cfg(unix)]
+mod ipc;
 mod library_service;
 mod lyrics;
 mod media_lib_cmd;
 mod metadata;
 mod models;
+#[cfg(target_os = "linux")]
+mod mpris;
+mod play_tracker;
+mod radio;
+mod remote;
+mod replaygain;
+mod scrobble_log;
+mod scrobbler;
+mod session;
+mod settings;
+mod sleep_timer;
 mod traits;
 
 use app_state::ManagedState;
+use settings::ManagedSettings;
 use tauri::Manager;
 use tauri_specta::{collect_commands, Builder};
 
 use crate::{
-    audio_player::{init_audio_player, track_progress, ManagedPlayer},
+    audio_player::{
+        init_audio_player, restore_session, save_session, track_progress, ManagedPlayer,
+    },
     constants::ensure_paths_created,
+    scrobbler::{run_scrobbler, ManagedScrobbler},
+    sleep_timer::run_sleep_timer,
 };
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -43,6 +63,11 @@ pub fn run() {
         media_lib_cmd::remove_from_playlist,
         media_lib_cmd::get_pl_id_by_name,
         media_lib_cmd::get_recently_added,
+        media_lib_cmd::get_most_played,
+        media_lib_cmd::get_recently_played,
+        media_lib_cmd::get_never_played,
+        media_lib_cmd::get_unplayable_tracks,
+        media_lib_cmd::clear_playback_error,
         media_lib_cmd::get_directories,
         media_lib_cmd::add_directory,
         media_lib_cmd::remove_directory,
@@ -53,6 +78,10 @@ pub fn run() {
         audio_player::next,
         audio_player::play_next,
         audio_player::play_list,
+        audio_player::play_album,
+        audio_player::play_artist,
+        audio_player::play_playlist,
+        audio_player::play_search,
         audio_player::add_to_queue,
         audio_player::clear,
         audio_player::pause,
@@ -61,8 +90,37 @@ pub fn run() {
         audio_player::add_list_to_queue,
         audio_player::play_list_next,
         audio_player::shuffle,
+        audio_player::set_shuffle_strategy,
         audio_player::previous,
         audio_player::remove_from_queue,
+        audio_player::skip_to_entry,
+        audio_player::undo_queue,
+        audio_player::redo_queue,
+        audio_player::set_loop_mode,
+        audio_player::set_autoplay,
+        audio_player::set_ab_loop,
+        audio_player::clear_ab_loop,
+        bookmarks::set_track_long_form,
+        bookmarks::set_dir_long_form,
+        bookmarks::get_long_form_dirs,
+        bookmarks::add_bookmark,
+        bookmarks::list_bookmarks,
+        bookmarks::delete_bookmark,
+        bookmarks::jump_to_bookmark,
+        sleep_timer::set_sleep_timer,
+        sleep_timer::stop_after_tracks,
+        sleep_timer::cancel_sleep_timer,
+        settings::get_settings,
+        settings::set_long_form_min_length,
+        settings::set_progress_interval,
+        remote::set_remote_control,
+        remote::regenerate_remote_token,
+        remote::get_remote_token,
+        scrobbler::set_lastfm,
+        scrobbler::lastfm_login,
+        scrobbler::lastfm_logout,
+        scrobbler::set_listenbrainz,
+        scrobbler::get_pending_scrobbles,
         library_service::fulltrack_from_id,
         lyrics::get_lyrics
     ]);
@@ -83,15 +141,35 @@ pub fn run() {
         .setup(|app| {
             let managed_state = ManagedState::new(app.handle().clone());
             app.manage(managed_state);
+            app.manage(ManagedSettings::new(app.handle().clone()));
 
             init_audio_player(app.handle().clone());
-            track_progress(app.handle().clone());
 
             app.manage(ManagedPlayer::new(app.handle().clone()));
+            track_progress(app.handle().clone());
+            run_sleep_timer(app.handle().clone());
+            app.manage(ManagedScrobbler::new());
+            run_scrobbler(app.handle().clone());
+            remote::init_remote(app.handle().clone());
+            #[cfg(unix)]
+            ipc::start_ipc(app.handle().clone());
+            #[cfg(target_os = "linux")]
+            mpris::start_mpris(app.handle().clone());
+            let app_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = restore_session(app_handle).await {
+                    eprintln!("Failed to restore the session: {}", e);
+                }
+            });
             Ok(())
         })
         .plugin(tauri_plugin_opener::init())
         .invoke_handler(builder.invoke_handler())
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app_handle, event| {
+            if let tauri::RunEvent::Exit = event {
+                tauri::async_runtime::block_on(save_session(app_handle.clone()));
+            }
+        });
 }
@@ -4,8 +4,10 @@ use std::sync::{Mutex, MutexGuard, OnceLock};
 
 use rusqlite::{params, Connection, OptionalExtension};
 
-use crate::error::{LibraryError, Result};
-use crate::models::{Album, Artist, FullTrack, Playlist, Track};
+use crate::error::{CommandResult, LibraryError, Result};
+use crate::models::{
+    Album, Artist, Bookmark, FullTrack, PendingScrobble, Playlist, ScrobbleService, Track,
+};
 
 // ---------------------------------------------------------------------------
 // Singletons
@@ -24,14 +26,8 @@ pub fn library_service() -> &'static Mutex<LibraryService> {
 // ---------------------------------------------------------------------------
 #[tauri::command]
 #[specta::specta]
-pub async fn fulltrack_from_id(id: i32) -> Option<FullTrack> {
-    if let Ok(library) = library_service().lock() {
-        if let Ok(result) = library.get_full_track_by_id(id.into()) {
-            return result;
-        }
-    }
-
-    None
+pub async fn fulltrack_from_id(id: i32) -> CommandResult<Option<FullTrack>> {
+    Ok(library_service().lock()?.get_full_track_by_id(id.into())?)
 }
 
 // ---------------------------------------------------------------------------
@@ -43,7 +39,9 @@ const FULL_TRACK_SELECT: &str = "
         t.*,
         r.name  AS artist_name,
         a.title AS album_title,
-        a.album_art
+        a.album_art,
+        a.genre,
+        a.year
     FROM tracks t
     JOIN artists r ON t.artist_id = r.id
     JOIN albums  a ON t.album_id  = a.id
@@ -134,6 +132,18 @@ impl LibraryService {
                 lyrics          TEXT,
                 composer        TEXT,
                 added_at        INTEGER,
+                track_gain      REAL,
+                track_peak      REAL,
+                album_gain      REAL,
+                album_peak      REAL,
+                play_count      INTEGER NOT NULL DEFAULT 0,
+                skip_count      INTEGER NOT NULL DEFAULT 0,
+                last_played     INTEGER,
+                long_form       INTEGER NOT NULL DEFAULT 0,
+                musicbrainz_recording_id TEXT,
+                start_offset    REAL,
+                end_offset      REAL,
+                playback_error  TEXT,
                 FOREIGN KEY (album_id)  REFERENCES albums  (id) ON DELETE CASCADE,
                 FOREIGN KEY (artist_id) REFERENCES artists (id) ON DELETE CASCADE
             );
@@ -156,12 +166,73 @@ impl LibraryService {
             CREATE TABLE IF NOT EXISTS directories (
                 path        TEXT
             );
+
+            CREATE TABLE IF NOT EXISTS plays (
+                id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                track_id    INTEGER NOT NULL,
+                kind        TEXT    NOT NULL,
+                played_at   INTEGER NOT NULL,
+                FOREIGN KEY (track_id) REFERENCES tracks (id) ON DELETE CASCADE
+            );
+
+            CREATE INDEX IF NOT EXISTS plays_track_id ON plays (track_id);
+
+            CREATE TABLE IF NOT EXISTS long_form_dirs (
+                path        TEXT PRIMARY KEY
+            );
+
+            CREATE TABLE IF NOT EXISTS track_positions (
+                track_id    INTEGER PRIMARY KEY,
+                position    REAL    NOT NULL,
+                updated_at  INTEGER NOT NULL,
+                FOREIGN KEY (track_id) REFERENCES tracks (id) ON DELETE CASCADE
+            );
+
+            CREATE TABLE IF NOT EXISTS bookmarks (
+                id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                track_id    INTEGER NOT NULL,
+                name        TEXT    NOT NULL,
+                position    REAL    NOT NULL,
+                created_at  INTEGER NOT NULL,
+                FOREIGN KEY (track_id) REFERENCES tracks (id) ON DELETE CASCADE
+            );
+
+            CREATE INDEX IF NOT EXISTS bookmarks_track_id ON bookmarks (track_id);
+
+            CREATE TABLE IF NOT EXISTS scrobble_queue (
+                id              INTEGER PRIMARY KEY AUTOINCREMENT,
+                service         TEXT    NOT NULL,
+                artist          TEXT    NOT NULL,
+                album           TEXT    NOT NULL,
+                title           TEXT    NOT NULL,
+                track_number    INTEGER NOT NULL,
+                duration        INTEGER NOT NULL,
+                timestamp       INTEGER NOT NULL,
+                mbid            TEXT,
+                attempts        INTEGER NOT NULL DEFAULT 0,
+                next_attempt    INTEGER NOT NULL DEFAULT 0
+            );
         ",
         )?;
 
         // Idempotent column migration for existing databases -- silently fails
         // if the column is already there, which is exactly what we want.
         let _ = conn.execute_batch("ALTER TABLE tracks ADD COLUMN added_at INTEGER;");
+        let _ = conn.execute_batch("ALTER TABLE tracks ADD COLUMN track_gain REAL;");
+        let _ = conn.execute_batch("ALTER TABLE tracks ADD COLUMN track_peak REAL;");
+        let _ = conn.execute_batch("ALTER TABLE tracks ADD COLUMN album_gain REAL;");
+        let _ = conn.execute_batch("ALTER TABLE tracks ADD COLUMN album_peak REAL;");
+        let _ = conn
+            .execute_batch("ALTER TABLE tracks ADD COLUMN play_count INTEGER NOT NULL DEFAULT 0;");
+        let _ = conn
+            .execute_batch("ALTER TABLE tracks ADD COLUMN skip_count INTEGER NOT NULL DEFAULT 0;");
+        let _ = conn.execute_batch("ALTER TABLE tracks ADD COLUMN last_played INTEGER;");
+        let _ = conn
+            .execute_batch("ALTER TABLE tracks ADD COLUMN long_form INTEGER NOT NULL DEFAULT 0;");
+        let _ = conn.execute_batch("ALTER TABLE tracks ADD COLUMN musicbrainz_recording_id TEXT;");
+        let _ = conn.execute_batch("ALTER TABLE tracks ADD COLUMN start_offset REAL;");
+        let _ = conn.execute_batch("ALTER TABLE tracks ADD COLUMN end_offset REAL;");
+        let _ = conn.execute_batch("ALTER TABLE tracks ADD COLUMN playback_error TEXT;");
 
         Ok(())
     }
@@ -188,6 +259,13 @@ impl LibraryService {
         isrc: Option<&str>,
         lyrics: Option<&str>,
         composer: Option<&str>,
+        track_gain: Option<f64>,
+        track_peak: Option<f64>,
+        album_gain: Option<f64>,
+        album_peak: Option<f64>,
+        musicbrainz_recording_id: Option<&str>,
+        start_offset: Option<f64>,
+        end_offset: Option<f64>,
     ) -> Result<()> {
         let conn = self.lock();
 
@@ -226,8 +304,10 @@ impl LibraryService {
             "INSERT INTO tracks (
                 album_id, artist_id, file_path, title,
                 track_number, disc_number, bpm, duration,
-                initial_key, isrc, lyrics, composer, added_at
-             ) VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13)
+                initial_key, isrc, lyrics, composer, added_at,
+                track_gain, track_peak, album_gain, album_peak,
+                musicbrainz_recording_id, start_offset, end_offset
+             ) VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,?14,?15,?16,?17,?18,?19,?20)
              ON CONFLICT(file_path) DO UPDATE SET
                 album_id     = excluded.album_id,
                 artist_id    = excluded.artist_id,
@@ -239,7 +319,14 @@ impl LibraryService {
                 initial_key  = excluded.initial_key,
                 isrc         = excluded.isrc,
                 lyrics       = excluded.lyrics,
-                composer     = excluded.composer",
+                composer     = excluded.composer,
+                track_gain   = excluded.track_gain,
+                track_peak   = excluded.track_peak,
+                album_gain   = excluded.album_gain,
+                album_peak   = excluded.album_peak,
+                musicbrainz_recording_id = excluded.musicbrainz_recording_id,
+                start_offset = excluded.start_offset,
+                end_offset   = excluded.end_offset",
             params![
                 album_id,
                 track_artist_id,
@@ -254,6 +341,13 @@ impl LibraryService {
                 lyrics,
                 composer,
                 now,
+                track_gain,
+                track_peak,
+                album_gain,
+                album_peak,
+                musicbrainz_recording_id,
+                start_offset,
+                end_offset,
             ],
         )?;
 
@@ -451,6 +545,29 @@ impl LibraryService {
         Ok(())
     }
 
+    /// Drops the entry for a whole file once a CUE sheet splits it into tracks.
+    pub fn delete_track_by_path(&self, file_path: &str) -> Result<()> {
+        let conn = self.lock();
+        conn.execute(
+            "DELETE FROM tracks WHERE file_path = ?1",
+            params![file_path],
+        )?;
+        Ok(())
+    }
+
+    /// Drops the tracks cut from a file whose CUE sheet has gone away. The
+    /// range on `#` and the next character up keeps this on the path index.
+    pub fn delete_cue_tracks(&self, file_path: &str) -> Result<()> {
+        let conn = self.lock();
+        conn.execute(
+            "DELETE FROM tracks
+             WHERE start_offset IS NOT NULL
+               AND file_path > ?1 || '#' AND file_path < ?1 || '$'",
+            params![file_path],
+        )?;
+        Ok(())
+    }
+
     /// Simple title / artist search. Case-insensitive, substring match.
     pub fn search_tracks(&self, query: &str) -> Result<Vec<FullTrack>> {
         let conn = self.lock();
@@ -465,6 +582,315 @@ impl LibraryService {
         rows.map(|r| r.map_err(Into::into)).collect()
     }
 
+    // -----------------------------------------------------------------------
+    // Listening history
+    // -----------------------------------------------------------------------
+
+    /// Logs a play and bumps the counters kept on the track itself.
+    pub fn record_play(&self, track_id: i64, played_at: i64) -> Result<()> {
+        let mut conn = self.lock();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO plays (track_id, kind, played_at) VALUES (?1, 'play', ?2)",
+            params![track_id, played_at],
+        )?;
+        tx.execute(
+            "UPDATE tracks SET play_count = play_count + 1, last_played = ?2 WHERE id = ?1",
+            params![track_id, played_at],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn record_skip(&self, track_id: i64, skipped_at: i64) -> Result<()> {
+        let mut conn = self.lock();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO plays (track_id, kind, played_at) VALUES (?1, 'skip', ?2)",
+            params![track_id, skipped_at],
+        )?;
+        tx.execute(
+            "UPDATE tracks SET skip_count = skip_count + 1 WHERE id = ?1",
+            params![track_id],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn get_most_played(&self, limit: i64) -> Result<Vec<FullTrack>> {
+        let conn = self.lock();
+        let sql = format!(
+            "{FULL_TRACK_SELECT}
+             WHERE t.play_count > 0
+             ORDER BY t.play_count DESC, t.last_played DESC
+             LIMIT ?1"
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params![limit], FullTrack::from_row)?;
+        rows.map(|r| r.map_err(Into::into)).collect()
+    }
+
+    pub fn get_recently_played(&self, limit: i64) -> Result<Vec<FullTrack>> {
+        let conn = self.lock();
+        let sql = format!(
+            "{FULL_TRACK_SELECT}
+             WHERE t.last_played IS NOT NULL
+             ORDER BY t.last_played DESC
+             LIMIT ?1"
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params![limit], FullTrack::from_row)?;
+        rows.map(|r| r.map_err(Into::into)).collect()
+    }
+
+    pub fn get_never_played(&self) -> Result<Vec<FullTrack>> {
+        let conn = self.lock();
+        let sql = format!(
+            "{FULL_TRACK_SELECT}
+             WHERE t.play_count = 0
+             ORDER BY t.added_at DESC"
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map([], FullTrack::from_row)?;
+        rows.map(|r| r.map_err(Into::into)).collect()
+    }
+
+    // -----------------------------------------------------------------------
+    // Unplayable tracks
+    // -----------------------------------------------------------------------
+
+    /// Flags a track that failed to load, `None` clears the flag.
+    pub fn set_playback_error(&self, track_id: i64, reason: Option<&str>) -> Result<()> {
+        let conn = self.lock();
+        conn.execute(
+            "UPDATE tracks SET playback_error = ?2 WHERE id = ?1",
+            params![track_id, reason],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_unplayable(&self) -> Result<Vec<FullTrack>> {
+        let conn = self.lock();
+        let sql = format!(
+            "{FULL_TRACK_SELECT}
+             WHERE t.playback_error IS NOT NULL
+             ORDER BY t.file_path ASC"
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map([], FullTrack::from_row)?;
+        rows.map(|r| r.map_err(Into::into)).collect()
+    }
+
+    // -----------------------------------------------------------------------
+    // Long-form audio
+    // -----------------------------------------------------------------------
+
+    pub fn set_track_long_form(&self, track_id: i64, long_form: bool) -> Result<()> {
+        let conn = self.lock();
+        conn.execute(
+            "UPDATE tracks SET long_form = ?2 WHERE id = ?1",
+            params![track_id, long_form],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_dir_long_form(&self, path: &str, long_form: bool) -> Result<()> {
+        let conn = self.lock();
+        if long_form {
+            conn.execute(
+                "INSERT OR IGNORE INTO long_form_dirs (path) VALUES (?1)",
+                params![path],
+            )?;
+        } else {
+            conn.execute("DELETE FROM long_form_dirs WHERE path = ?1", params![path])?;
+        }
+        Ok(())
+    }
+
+    pub fn get_long_form_dirs(&self) -> Result<Vec<String>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare("SELECT path FROM long_form_dirs ORDER BY path")?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.map(|r| r.map_err(Into::into)).collect()
+    }
+
+    /// Whether the track was flagged itself or lives under a flagged directory.
+    pub fn is_long_form(&self, track_id: i64) -> Result<bool> {
+        let conn = self.lock();
+        let result = conn
+            .query_row(
+                // The separator keeps `/books` from also matching `/books-old`
+                "SELECT t.long_form OR EXISTS (
+                SELECT 1 FROM long_form_dirs d
+                WHERE substr(t.file_path, 1, length(rtrim(d.path, ?2)) + 1) = rtrim(d.path, ?2) || ?2
+             )
+             FROM tracks t WHERE t.id = ?1",
+                params![track_id, std::path::MAIN_SEPARATOR_STR],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(result.unwrap_or(false))
+    }
+
+    pub fn save_track_position(&self, track_id: i64, position: f64) -> Result<()> {
+        let conn = self.lock();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0);
+        conn.execute(
+            "INSERT INTO track_positions (track_id, position, updated_at)
+             VALUES (?1, ?2, ?3)
+             ON CONFLICT(track_id) DO UPDATE SET
+                position   = excluded.position,
+                updated_at = excluded.updated_at",
+            params![track_id, position, now],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_track_position(&self, track_id: i64) -> Result<Option<f64>> {
+        let conn = self.lock();
+        let result = conn
+            .query_row(
+                "SELECT position FROM track_positions WHERE track_id = ?1",
+                params![track_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(result)
+    }
+
+    pub fn delete_track_position(&self, track_id: i64) -> Result<()> {
+        let conn = self.lock();
+        conn.execute(
+            "DELETE FROM track_positions WHERE track_id = ?1",
+            params![track_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn add_bookmark(&self, track_id: i64, name: &str, position: f64) -> Result<i64> {
+        let conn = self.lock();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0);
+        conn.execute(
+            "INSERT INTO bookmarks (track_id, name, position, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![track_id, name, position, now],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn get_bookmarks(&self, track_id: i64) -> Result<Vec<Bookmark>> {
+        let conn = self.lock();
+        let mut stmt =
+            conn.prepare("SELECT * FROM bookmarks WHERE track_id = ?1 ORDER BY position ASC")?;
+        let rows = stmt.query_map(params![track_id], Bookmark::from_row)?;
+        rows.map(|r| r.map_err(Into::into)).collect()
+    }
+
+    pub fn get_bookmark(&self, id: i64) -> Result<Option<Bookmark>> {
+        let conn = self.lock();
+        let result = conn
+            .query_row(
+                "SELECT * FROM bookmarks WHERE id = ?1",
+                params![id],
+                Bookmark::from_row,
+            )
+            .optional()?;
+        Ok(result)
+    }
+
+    pub fn delete_bookmark(&self, id: i64) -> Result<()> {
+        let conn = self.lock();
+        conn.execute("DELETE FROM bookmarks WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    // -----------------------------------------------------------------------
+    // Scrobble queue
+    // -----------------------------------------------------------------------
+
+    pub fn enqueue_scrobble(&self, scrobble: &PendingScrobble) -> Result<()> {
+        let conn = self.lock();
+        conn.execute(
+            "INSERT INTO scrobble_queue (
+                service, artist, album, title, track_number, duration, timestamp, mbid
+             ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                scrobble.service.as_str(),
+                scrobble.artist,
+                scrobble.album,
+                scrobble.title,
+                scrobble.track_number,
+                scrobble.duration,
+                scrobble.timestamp,
+                scrobble.mbid,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// A service's scrobbles whose backoff has run out, oldest listens first.
+    pub fn get_due_scrobbles(
+        &self,
+        service: ScrobbleService,
+        now: i64,
+        limit: i64,
+    ) -> Result<Vec<PendingScrobble>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT * FROM scrobble_queue
+             WHERE service = ?1 AND next_attempt <= ?2
+             ORDER BY timestamp ASC
+             LIMIT ?3",
+        )?;
+        let rows = stmt.query_map(
+            params![service.as_str(), now, limit],
+            PendingScrobble::from_row,
+        )?;
+        rows.map(|r| r.map_err(Into::into)).collect()
+    }
+
+    pub fn get_all_scrobbles(&self) -> Result<Vec<PendingScrobble>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare("SELECT * FROM scrobble_queue ORDER BY timestamp ASC")?;
+        let rows = stmt.query_map([], PendingScrobble::from_row)?;
+        rows.map(|r| r.map_err(Into::into)).collect()
+    }
+
+    /// When the next scrobble queued for one of `services` is due, if there are any.
+    pub fn next_scrobble_attempt(&self, services: &[ScrobbleService]) -> Result<Option<i64>> {
+        let conn = self.lock();
+        let mut next = Vec::new();
+        for service in services {
+            let due: Option<i64> = conn.query_row(
+                "SELECT MIN(next_attempt) FROM scrobble_queue WHERE service = ?1",
+                params![service.as_str()],
+                |row| row.get(0),
+            )?;
+            next.extend(due);
+        }
+        Ok(next.into_iter().min())
+    }
+
+    pub fn postpone_scrobble(&self, id: i64, next_attempt: i64) -> Result<()> {
+        let conn = self.lock();
+        conn.execute(
+            "UPDATE scrobble_queue SET attempts = attempts + 1, next_attempt = ?2 WHERE id = ?1",
+            params![id, next_attempt],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_scrobble(&self, id: i64) -> Result<()> {
+        let conn = self.lock();
+        conn.execute("DELETE FROM scrobble_queue WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
     // -----------------------------------------------------------------------
     // Playlists
     // -----------------------------------------------------------------------
@@ -526,16 +952,16 @@ impl LibraryService {
         Ok(())
     }
 
-    pub fn get_playlist_by_id(&self, id: i64) -> Option<Playlist> {
+    pub fn get_playlist_by_id(&self, id: i64) -> Result<Option<Playlist>> {
         let conn = self.lock();
-        conn.query_row(
-            "SELECT * FROM playlists WHERE id = ?1",
-            params![id],
-            Playlist::from_row,
-        )
-        .optional()
-        .ok()
-        .flatten()
+        let result = conn
+            .query_row(
+                "SELECT * FROM playlists WHERE id = ?1",
+                params![id],
+                Playlist::from_row,
+            )
+            .optional()?;
+        Ok(result)
     }
 
     // -----------------------------------------------------------------------
@@ -640,7 +1066,8 @@ impl LibraryService {
     pub fn get_tracks_in_playlist(&self, playlist_id: i64) -> Result<Vec<FullTrack>> {
         let conn = self.lock();
         let sql = "
-            SELECT t.*, pt.position, r.name AS artist_name, a.title AS album_title, a.album_art
+            SELECT t.*, pt.position, r.name AS artist_name, a.title AS album_title, a.album_art,
+                   a.genre, a.year
             FROM tracks t
             JOIN playlist_tracks pt ON t.id = pt.track_id
             JOIN artists r ON t.artist_id = r.id
@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 use specta::Type;
 use std::fs;
 
-use crate::models::FullTrack;
+use crate::{error::CommandResult, models::FullTrack};
 
 // ==================== Data Structures ====================
 
@@ -62,7 +62,11 @@ pub struct SyllableWord {
 
 #[tauri::command]
 #[specta::specta]
-pub async fn get_lyrics(track: FullTrack) -> Lyrics {
+pub async fn get_lyrics(track: FullTrack) -> CommandResult<Lyrics> {
+    Ok(find_lyrics(&track))
+}
+
+fn find_lyrics(track: &FullTrack) -> Lyrics {
     let path = &track.track.file_path;
     let ttml_path = swap_extension(path, "ttml");
     let lrc_path = swap_extension(path, "lrc");
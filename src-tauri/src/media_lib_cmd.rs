@@ -1,67 +1,88 @@
 use std::collections::HashMap;
-use tauri::{AppHandle, Emitter};
+use tauri::{AppHandle, Emitter, Manager};
 
 use crate::{
+    audio_player::{self, ManagedPlayer, PlaybackContext},
+    error::{CommandError, CommandResult},
     library_service::library_service,
     models::{Album, Artist, FullTrack, MatchReason, Playlist, SearchResults, Track, TrackResult},
 };
 
 #[tauri::command]
 #[specta::specta]
-pub async fn get_directories() -> Vec<String> {
-    if let Ok(library) = library_service().lock() {
-        if let Ok(dirs) = library.get_directories() {
-            return dirs
-                .into_iter()
-                .map(|p| p.to_string_lossy().to_string())
-                .collect();
-        }
-    }
-    Vec::new()
+pub async fn get_directories() -> CommandResult<Vec<String>> {
+    let dirs = library_service().lock()?.get_directories()?;
+    Ok(dirs
+        .into_iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect())
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn add_directory(app_handle: AppHandle, path: String) {
-    if let Ok(library) = library_service().lock() {
-        _ = library.add_directory(&path);
-        _ = app_handle.emit("directories-changed", ());
-    }
+pub async fn add_directory(app_handle: AppHandle, path: String) -> CommandResult<()> {
+    library_service().lock()?.add_directory(&path)?;
+    _ = app_handle.emit("directories-changed", ());
+    Ok(())
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn remove_directory(app_handle: AppHandle, path: String) {
-    if let Ok(library) = library_service().lock() {
-        _ = library.delete_directory(&path);
-        _ = app_handle.emit("directories-changed", ());
-    }
+pub async fn remove_directory(app_handle: AppHandle, path: String) -> CommandResult<()> {
+    library_service().lock()?.delete_directory(&path)?;
+    _ = app_handle.emit("directories-changed", ());
+    Ok(())
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn get_recently_added() -> Vec<Album> {
-    if let Ok(library) = library_service().lock() {
-        if let Ok(recently_added) = library.get_recently_added_albums() {
-            return recently_added;
-        }
-    }
+pub async fn get_recently_added() -> CommandResult<Vec<Album>> {
+    Ok(library_service().lock()?.get_recently_added_albums()?)
+}
 
-    Vec::new()
+#[tauri::command]
+#[specta::specta]
+pub async fn get_most_played(limit: i32) -> CommandResult<Vec<FullTrack>> {
+    Ok(library_service().lock()?.get_most_played(limit.into())?)
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn get_pl_id_by_name(name: String) -> Option<i32> {
-    if let Ok(library) = library_service().lock() {
-        if let Ok(result) = library.get_playlist_id_by_name(name.as_str()) {
-            if let Some(id) = result {
-                return Some(id as i32);
-            }
-        }
-    }
+pub async fn get_recently_played(limit: i32) -> CommandResult<Vec<FullTrack>> {
+    Ok(library_service()
+        .lock()?
+        .get_recently_played(limit.into())?)
+}
 
-    None
+#[tauri::command]
+#[specta::specta]
+pub async fn get_never_played() -> CommandResult<Vec<FullTrack>> {
+    Ok(library_service().lock()?.get_never_played()?)
+}
+
+/// Tracks that failed to load last time they were tried.
+#[tauri::command]
+#[specta::specta]
+pub async fn get_unplayable_tracks() -> CommandResult<Vec<FullTrack>> {
+    Ok(library_service().lock()?.get_unplayable()?)
+}
+
+/// Dismisses the failure flag, for files that have been fixed or replaced.
+#[tauri::command]
+#[specta::specta]
+pub async fn clear_playback_error(track_id: i64) -> CommandResult<()> {
+    Ok(library_service()
+        .lock()?
+        .set_playback_error(track_id, None)?)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_pl_id_by_name(name: String) -> CommandResult<Option<i32>> {
+    let id = library_service()
+        .lock()?
+        .get_playlist_id_by_name(name.as_str())?;
+    Ok(id.map(|id| id as i32))
 }
 
 #[tauri::command]
@@ -71,15 +92,15 @@ pub async fn remove_from_playlist(
     position: i32,
     playlist_id: i32,
     app_handle: AppHandle,
-) {
-    if let Ok(library) = library_service().lock() {
-        _ = library.remove_track_from_playlist(
-            playlist_id.into(),
-            track_id.into(),
-            position.into(),
-        );
-        _ = app_handle.emit("playlist-updated", playlist_id);
-    }
+) -> CommandResult<()> {
+    library_service().lock()?.remove_track_from_playlist(
+        playlist_id.into(),
+        track_id.into(),
+        position.into(),
+    )?;
+    _ = app_handle.emit("playlist-updated", playlist_id);
+    playlist_changed(&app_handle, playlist_id.into()).await;
+    Ok(())
 }
 
 #[tauri::command]
@@ -89,44 +110,54 @@ pub async fn add_to_playlist(
     playlist_id: Option<i32>,
     target_playlist_id: i32,
     app_handle: AppHandle,
-) {
+) -> CommandResult<()> {
+    let library = library_service().lock()?;
+
     if let Some(id) = track_id {
-        if let Ok(library) = library_service().lock() {
-            _ = library.add_track_to_playlist(target_playlist_id.into(), id.into());
-        }
+        library.add_track_to_playlist(target_playlist_id.into(), id.into())?;
     }
 
     if let Some(p_id) = playlist_id {
-        if let Ok(library) = library_service().lock() {
-            if let Ok(tracks) = library.get_tracks_in_playlist(p_id.into()) {
-                for track in tracks {
-                    _ = library
-                        .add_track_to_playlist(target_playlist_id.into(), track.track.id.unwrap());
-                }
+        for track in library.get_tracks_in_playlist(p_id.into())? {
+            if let Some(id) = track.track.id {
+                library.add_track_to_playlist(target_playlist_id.into(), id)?;
             }
         }
     }
 
+    drop(library);
+
     _ = app_handle.emit("playlist-updated", target_playlist_id);
+    playlist_changed(&app_handle, target_playlist_id.into()).await;
+    Ok(())
+}
+
+/// Keeps the queue in step with a playlist that's being played from.
+async fn playlist_changed(app_handle: &AppHandle, playlist_id: i64) {
+    audio_player::refresh_context(
+        &app_handle.state::<ManagedPlayer>(),
+        |context| matches!(context, PlaybackContext::Playlist { id, .. } if *id == playlist_id),
+    )
+    .await;
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn delete_playlist(app_handle: AppHandle, id: i32) {
-    let _ = library_service().lock().map(|library| {
-        _ = library.delete_playlist(id as i64);
-
-        _ = app_handle.emit("playlists-changed", ());
-    });
+pub async fn delete_playlist(app_handle: AppHandle, id: i32) -> CommandResult<()> {
+    library_service().lock()?.delete_playlist(id as i64)?;
+    _ = app_handle.emit("playlists-changed", ());
+    playlist_changed(&app_handle, id.into()).await;
+    Ok(())
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn create_playlist(app_handle: AppHandle, name: String) {
-    let _ = library_service().lock().map(|library| {
-        _ = library.create_playlist(name.as_str(), None);
-        _ = app_handle.emit("playlists-changed", ());
-    });
+pub async fn create_playlist(app_handle: AppHandle, name: String) -> CommandResult<()> {
+    library_service()
+        .lock()?
+        .create_playlist(name.as_str(), None)?;
+    _ = app_handle.emit("playlists-changed", ());
+    Ok(())
 }
 
 fn score_track(track: &FullTrack, term: &str) -> u32 {
@@ -174,22 +205,20 @@ fn score_track(track: &FullTrack, term: &str) -> u32 {
 
 #[tauri::command]
 #[specta::specta]
-pub async fn search(term: String) -> SearchResults {
+pub async fn search(term: String) -> CommandResult<SearchResults> {
     let trimmed = term.trim().to_string();
     if trimmed.is_empty() {
-        return SearchResults::default();
+        return Ok(SearchResults::default());
     }
 
     let term_lower = trimmed.to_lowercase();
 
-    let Ok(library) = library_service().lock() else {
-        return SearchResults::default();
-    };
+    let library = library_service().lock()?;
 
-    let tracks = library.get_all_tracks().unwrap_or_default();
-    let albums = library.get_all_albums().unwrap_or_default();
-    let artists = library.get_all_artists().unwrap_or_default();
-    let playlists = library.get_all_playlists().unwrap_or_default();
+    let tracks = library.get_all_tracks()?;
+    let albums = library.get_all_albums()?;
+    let artists = library.get_all_artists()?;
+    let playlists = library.get_all_playlists()?;
 
     drop(library);
 
@@ -268,141 +297,86 @@ pub async fn search(term: String) -> SearchResults {
         .filter(|p| p.name.to_lowercase().contains(&term_lower))
         .collect();
 
-    SearchResults {
+    Ok(SearchResults {
         tracks: ranked_tracks,
         albums: filtered_albums,
         artists: filtered_artists,
         playlists: filtered_playlists,
-    }
+    })
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn get_playlist(id: i32) -> Option<Playlist> {
-    if let Ok(library) = library_service().lock() {
-        if let Some(playlist) = library.get_playlist_by_id(id.into()) {
-            return Some(playlist);
-        }
-    }
-    None
+pub async fn get_playlist(id: i32) -> CommandResult<Playlist> {
+    library_service()
+        .lock()?
+        .get_playlist_by_id(id.into())?
+        .ok_or_else(|| CommandError::not_found(format!("Playlist {id}")))
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn get_all_playlists() -> Vec<Playlist> {
-    if let Ok(library) = library_service().lock() {
-        if let Ok(playlists) = library.get_all_playlists() {
-            return playlists;
-        }
-    }
-    Vec::new()
+pub async fn get_all_playlists() -> CommandResult<Vec<Playlist>> {
+    Ok(library_service().lock()?.get_all_playlists()?)
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn get_playlist_tracks(playlist_id: i32) -> Vec<FullTrack> {
-    if let Ok(library) = library_service().lock() {
-        if let Ok(playlist_tracks) = library.get_tracks_in_playlist(playlist_id.into()) {
-            return playlist_tracks;
-        }
-    }
-    Vec::new()
+pub async fn get_playlist_tracks(playlist_id: i32) -> CommandResult<Vec<FullTrack>> {
+    Ok(library_service()
+        .lock()?
+        .get_tracks_in_playlist(playlist_id.into())?)
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn get_artist_albums(id: i32) -> Vec<Album> {
-    if let Ok(library) = library_service().lock() {
-        if let Ok(albums) = library.get_albums_by_artist(id.into()) {
-            return albums;
-        }
-    }
-    Vec::new()
+pub async fn get_artist_albums(id: i32) -> CommandResult<Vec<Album>> {
+    Ok(library_service().lock()?.get_albums_by_artist(id.into())?)
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn get_all_artists() -> Vec<Artist> {
-    if let Ok(library) = library_service().lock() {
-        if let Ok(artists) = library.get_all_artists() {
-            let mut final_artists: Vec<Artist> = Vec::new();
-            for artist in artists {
-                if let Ok(albums) = library.get_albums_by_artist(artist.id.unwrap()) {
-                    if !albums.is_empty() {
-                        final_artists.push(artist);
-                    }
-                }
-            }
-            return final_artists;
+pub async fn get_all_artists() -> CommandResult<Vec<Artist>> {
+    let library = library_service().lock()?;
+
+    let mut final_artists: Vec<Artist> = Vec::new();
+    for artist in library.get_all_artists()? {
+        let Some(id) = artist.id else {
+            continue;
+        };
+        if !library.get_albums_by_artist(id)?.is_empty() {
+            final_artists.push(artist);
         }
     }
-    Vec::new()
+
+    Ok(final_artists)
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn get_album_tracks(album_id: i32) -> Vec<Track> {
-    match library_service().lock() {
-        Ok(service) => service
-            .get_tracks_by_album(album_id.into())
-            .unwrap_or_default(),
-        Err(e) => {
-            eprintln!("Failed to lock db mutex: {}", e);
-            Vec::new()
-        }
-    }
+pub async fn get_album_tracks(album_id: i32) -> CommandResult<Vec<Track>> {
+    Ok(library_service()
+        .lock()?
+        .get_tracks_by_album(album_id.into())?)
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn get_artist_by_id(id: i32) -> Artist {
-    match library_service().lock() {
-        Ok(service) => service
-            .get_artist_by_id(id.into())
-            .ok()
-            .flatten()
-            .unwrap_or_else(|| Artist {
-                id: None,
-                name: "Unknown Artist".into(),
-                genre: None,
-            }),
-        Err(e) => {
-            eprintln!("Failed to lock library: {}", e);
-            Artist {
-                id: None,
-                name: "Unknown Artist".into(),
-                genre: None,
-            }
-        }
-    }
+pub async fn get_artist_by_id(id: i32) -> CommandResult<Artist> {
+    library_service()
+        .lock()?
+        .get_artist_by_id(id.into())?
+        .ok_or_else(|| CommandError::not_found(format!("Artist {id}")))
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn get_all_albums() -> Vec<Album> {
-    match library_service().lock() {
-        Ok(service) => service.get_all_albums().unwrap_or_else(|e| {
-            eprintln!("{}", e);
-            Vec::new()
-        }),
-        Err(e) => {
-            eprintln!("Failed to lock library: {}", e);
-            Vec::new()
-        }
-    }
+pub async fn get_all_albums() -> CommandResult<Vec<Album>> {
+    Ok(library_service().lock()?.get_all_albums()?)
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn get_all_tracks() -> Vec<FullTrack> {
-    match library_service().lock() {
-        Ok(service) => service.get_all_tracks().unwrap_or_else(|e| {
-            eprintln!("{}", e);
-            Vec::new()
-        }),
-        Err(e) => {
-            eprintln!("Error locking library: {}", e);
-            Vec::new()
-        }
-    }
+pub async fn get_all_tracks() -> CommandResult<Vec<FullTrack>> {
+    Ok(library_service().lock()?.get_all_tracks()?)
 }
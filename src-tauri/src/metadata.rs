@@ -1,7 +1,13 @@
+use crate::audio_player::{self, ManagedPlayer};
 use crate::constants;
 use crate::constants::cover_cache;
+use crate::cue;
+use crate::error::CommandResult;
 use crate::library_service::{library_service, LibraryService};
 use crate::models::FileMetadata;
+use crate::replaygain;
+use lofty::config::ParseOptions;
+use lofty::ogg::OpusFile;
 use lofty::picture::PictureType;
 use lofty::prelude::*;
 use lofty::probe::Probe;
@@ -10,53 +16,57 @@ use std::collections::{HashSet, VecDeque};
 use std::fs::File;
 use std::io::{BufRead, BufReader, Write};
 use std::path::{Path, PathBuf};
-use tauri::{AppHandle, Emitter};
+use tauri::{AppHandle, Emitter, Manager};
 use walkdir::WalkDir;
 
 #[tauri::command]
 #[specta::specta]
-pub async fn index(app_handle: AppHandle) {
-    index_tracks();
-    delete_non_existant();
-    index_playlists();
+pub async fn index(app_handle: AppHandle) -> CommandResult<()> {
+    index_tracks()?;
+    delete_non_existant()?;
+    index_playlists()?;
+
+    // Albums and search results may have gained or lost tracks
+    audio_player::refresh_context(&app_handle.state::<ManagedPlayer>(), |_| true).await;
 
     _ = app_handle.emit("indexing-done", ());
+    Ok(())
 }
 
-pub fn delete_non_existant() {
-    if let Ok(library) = library_service().lock() {
-        if let Ok(tracks) = library.get_all_tracks() {
-            for track in tracks {
-                if !Path::new(&track.track.file_path).exists() {
-                    _ = library.delete_track(track.track.id.unwrap());
-                }
+pub fn delete_non_existant() -> CommandResult<()> {
+    let library = library_service().lock()?;
+
+    for track in library.get_all_tracks()? {
+        if let Some(id) = track.track.id {
+            if !Path::new(track.track.audio_path()).exists() {
+                library.delete_track(id)?;
             }
         }
     }
+
+    Ok(())
 }
 
-pub fn index_tracks() {
+pub fn index_tracks() -> CommandResult<()> {
     println!("Begin indexing tracks");
 
+    let sheets = cue::SheetCache::default();
     let parsed: Vec<FileMetadata> = get_all_audio_files()
         .into_par_iter()
         .filter_map(parse_and_write_cover)
+        .flat_map_iter(|meta| cue::split(meta, &sheets))
         .collect();
 
-    let service = library_service();
-    let guard = match service.lock() {
-        Ok(g) => g,
-        Err(e) => {
-            eprintln!("Mutex poisoned: {:?}", e);
-            return;
-        }
-    };
+    let guard = library_service().lock()?;
+    // Files cut into CUE tracks whose whole-file entry is already gone
+    let mut split_files = HashSet::new();
 
     for meta in parsed {
-        index_file_to_db(&guard, meta);
+        index_file_to_db(&guard, meta, &mut split_files);
     }
 
     println!("Done indexing tracks");
+    Ok(())
 }
 
 fn parse_and_write_cover(file: PathBuf) -> Option<FileMetadata> {
@@ -120,6 +130,14 @@ fn parse_and_write_cover(file: PathBuf) -> Option<FileMetadata> {
             }
         });
 
+    let parse_gain = |key: ItemKey| tag.get_string(key).and_then(replaygain::parse_value);
+
+    // Opus files carry R128 gains instead, only fall back to them when there's no ReplayGain
+    let (r128_track, r128_album) = match file.extension().and_then(|e| e.to_str()) {
+        Some("opus") => read_r128(&file),
+        _ => (None, None),
+    };
+
     Some(FileMetadata {
         path: file,
         title: tag.title().map(|s| s.to_string()),
@@ -137,10 +155,38 @@ fn parse_and_write_cover(file: PathBuf) -> Option<FileMetadata> {
         lyrics: tag.get_string(ItemKey::Lyrics).map(str::to_owned),
         composer: tag.get_string(ItemKey::Composer).map(str::to_owned),
         cover_path,
+        track_gain: parse_gain(ItemKey::ReplayGainTrackGain).or(r128_track),
+        track_peak: parse_gain(ItemKey::ReplayGainTrackPeak),
+        album_gain: parse_gain(ItemKey::ReplayGainAlbumGain).or(r128_album),
+        album_peak: parse_gain(ItemKey::ReplayGainAlbumPeak),
+        musicbrainz_recording_id: tag
+            .get_string(ItemKey::MusicBrainzRecordingId)
+            .map(str::to_owned),
+        start_offset: None,
+        end_offset: None,
     })
 }
 
-fn index_file_to_db(guard: &LibraryService, meta: FileMetadata) {
+fn read_r128(file: &Path) -> (Option<f64>, Option<f64>) {
+    let Ok(mut reader) = File::open(file) else {
+        return (None, None);
+    };
+    let Ok(opus) = OpusFile::read_from(&mut reader, ParseOptions::new()) else {
+        return (None, None);
+    };
+
+    let comments = opus.vorbis_comments();
+    (
+        comments
+            .get("R128_TRACK_GAIN")
+            .and_then(replaygain::parse_r128),
+        comments
+            .get("R128_ALBUM_GAIN")
+            .and_then(replaygain::parse_r128),
+    )
+}
+
+fn index_file_to_db(guard: &LibraryService, meta: FileMetadata, split_files: &mut HashSet<String>) {
     let path_str = match meta.path.to_str() {
         Some(s) => s,
         None => {
@@ -158,6 +204,18 @@ fn index_file_to_db(guard: &LibraryService, meta: FileMetadata) {
         }
     }
 
+    // A CUE sheet was added or removed since the file was last indexed
+    match path_str.rsplit_once('#') {
+        Some((audio, _)) if meta.start_offset.is_some() => {
+            if split_files.insert(audio.to_string()) {
+                _ = guard.delete_track_by_path(audio);
+            }
+        }
+        _ => {
+            _ = guard.delete_cue_tracks(path_str);
+        }
+    }
+
     if let Err(e) = guard.add_track_with_metadata(
         path_str,
         meta.title.as_deref(),
@@ -175,6 +233,13 @@ fn index_file_to_db(guard: &LibraryService, meta: FileMetadata) {
         meta.isrc.as_deref(),
         meta.lyrics.as_deref(),
         meta.composer.as_deref(),
+        meta.track_gain,
+        meta.track_peak,
+        meta.album_gain,
+        meta.album_peak,
+        meta.musicbrainz_recording_id.as_deref(),
+        meta.start_offset,
+        meta.end_offset,
     ) {
         eprintln!("{}", e);
         return;
@@ -201,47 +266,40 @@ fn index_file_to_db(guard: &LibraryService, meta: FileMetadata) {
     }
 }
 
-pub fn index_playlists() {
+pub fn index_playlists() -> CommandResult<()> {
     println!("Begin indexing playlists");
 
-    let files = get_m3u8_files();
+    for file in get_m3u8_files() {
+        let Some(filename) = file.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
 
-    for file in files {
-        let mut playlist_id: Option<i64> = None;
-
-        if let Some(filename) = file.file_stem().and_then(|s| s.to_str()) {
-            if let Ok(library) = library_service().lock() {
-                if !library.playlist_exists(filename).unwrap() {
-                    _ = library.create_playlist(filename, None);
-                }
-
-                if let Ok(result) = library.get_playlist_id_by_name(filename) {
-                    playlist_id = result;
-                }
-            }
+        let library = library_service().lock()?;
+        if !library.playlist_exists(filename)? {
+            library.create_playlist(filename, None)?;
         }
+        let Some(playlist_id) = library.get_playlist_id_by_name(filename)? else {
+            continue;
+        };
+        drop(library);
+
+        for audio_file in get_playlist_track_paths(file) {
+            let Some(path) = audio_file.to_str() else {
+                continue;
+            };
 
-        let audio_files = get_playlist_track_paths(file);
-
-        for audio_file in audio_files {
-            if let Ok(library) = library_service().lock() {
-                if let Some(p_id) = playlist_id {
-                    if !library
-                        .is_track_in_playlist(p_id, audio_file.to_str().unwrap_or(""))
-                        .unwrap_or(false)
-                    {
-                        if let Some(track_id) =
-                            library.get_track_id_by_path(audio_file.to_str().unwrap())
-                        {
-                            _ = library.add_track_to_playlist(p_id, track_id);
-                        }
-                    }
-                }
+            let library = library_service().lock()?;
+            if library.is_track_in_playlist(playlist_id, path)? {
+                continue;
+            }
+            if let Some(track_id) = library.get_track_id_by_path(path) {
+                library.add_track_to_playlist(playlist_id, track_id)?;
             }
         }
     }
 
     println!("End indexing playlists");
+    Ok(())
 }
 
 fn get_playlist_track_paths(m3u8_file: PathBuf) -> VecDeque<PathBuf> {
@@ -21,6 +21,13 @@ pub struct FileMetadata {
     pub lyrics: Option<String>,
     pub composer: Option<String>,
     pub cover_path: Option<PathBuf>,
+    pub track_gain: Option<f64>,
+    pub track_peak: Option<f64>,
+    pub album_gain: Option<f64>,
+    pub album_peak: Option<f64>,
+    pub musicbrainz_recording_id: Option<String>,
+    pub start_offset: Option<f64>,
+    pub end_offset: Option<f64>,
 }
 
 // ---------------------------------------------------------------------------
@@ -118,6 +125,18 @@ pub struct Track {
     pub lyrics: Option<String>,
     pub composer: Option<String>,
     pub added_at: Option<i64>, // Unix ms timestamp; None for tracks added before this field existed
+    pub track_gain: Option<f64>, // ReplayGain dB; R128 tags are converted on import
+    pub track_peak: Option<f64>,
+    pub album_gain: Option<f64>,
+    pub album_peak: Option<f64>,
+    pub play_count: i64,
+    pub skip_count: i64,
+    pub last_played: Option<i64>, // Unix ms timestamp
+    pub long_form: bool,
+    pub musicbrainz_recording_id: Option<String>,
+    pub start_offset: Option<f64>, // seconds into the file, only set for tracks from a CUE sheet
+    pub end_offset: Option<f64>,   // None plays to the end of the file
+    pub playback_error: Option<String>, // why the last attempt to load it failed, if it did
 }
 
 impl Track {
@@ -139,9 +158,38 @@ impl Track {
             lyrics: row.get("lyrics")?,
             composer: row.get("composer")?,
             added_at: row.get("added_at")?,
+            track_gain: row.get("track_gain")?,
+            track_peak: row.get("track_peak")?,
+            album_gain: row.get("album_gain")?,
+            album_peak: row.get("album_peak")?,
+            play_count: row.get::<_, Option<i64>>("play_count")?.unwrap_or(0),
+            skip_count: row.get::<_, Option<i64>>("skip_count")?.unwrap_or(0),
+            last_played: row.get("last_played")?,
+            long_form: row.get::<_, Option<bool>>("long_form")?.unwrap_or(false),
+            musicbrainz_recording_id: row.get("musicbrainz_recording_id")?,
+            start_offset: row.get("start_offset")?,
+            end_offset: row.get("end_offset")?,
+            playback_error: row.get("playback_error")?,
         })
     }
 
+    /// The file the audio lives in. Tracks from a CUE sheet share one and carry
+    /// a `#nn` suffix on top of it.
+    pub fn audio_path(&self) -> &str {
+        match self.start_offset {
+            Some(_) => self
+                .file_path
+                .rsplit_once('#')
+                .map_or(self.file_path.as_str(), |(path, _)| path),
+            None => &self.file_path,
+        }
+    }
+
+    /// Where the track begins within its file.
+    pub fn start(&self) -> f64 {
+        self.start_offset.unwrap_or(0.0)
+    }
+
     /// Format duration (milliseconds) into a human-readable string.
     pub fn formatted_duration(&self) -> String {
         if self.duration <= 0 {
@@ -171,6 +219,8 @@ pub struct FullTrack {
     pub album_title: String,
     pub album_art: Option<String>,
     pub playlist_position: Option<i64>,
+    pub genre: Option<String>,
+    pub year: i64,
 }
 
 impl FullTrack {
@@ -185,6 +235,145 @@ impl FullTrack {
                 .unwrap_or_else(|| "Unknown Album".into()),
             album_art: row.get("album_art")?,
             playlist_position: row.get("position").ok(), // only present in playlist queries
+            genre: row.get("genre")?,
+            year: row.get::<_, Option<i64>>("year")?.unwrap_or(0),
+        })
+    }
+
+    /// A bare track for tests, with only the ids and a title filled in.
+    #[cfg(test)]
+    pub fn stub(id: i64, album_id: i64) -> Self {
+        Self {
+            track: Track {
+                id: Some(id),
+                album_id,
+                artist_id: 0,
+                file_path: format!("/music/{id}.flac"),
+                title: format!("Track {id}"),
+                track_number: 0,
+                disc_number: 1,
+                bpm: 0,
+                duration: 0,
+                initial_key: None,
+                isrc: None,
+                lyrics: None,
+                composer: None,
+                added_at: None,
+                track_gain: None,
+                track_peak: None,
+                album_gain: None,
+                album_peak: None,
+                play_count: 0,
+                skip_count: 0,
+                last_played: None,
+                long_form: false,
+                musicbrainz_recording_id: None,
+                start_offset: None,
+                end_offset: None,
+                playback_error: None,
+            },
+            artist_name: String::new(),
+            album_title: String::new(),
+            album_art: None,
+            playlist_position: None,
+            genre: None,
+            year: 0,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Bookmark
+// ---------------------------------------------------------------------------
+
+/// A named point of interest inside a track.
+#[derive(Clone, Serialize, Deserialize, Debug, Type)]
+pub struct Bookmark {
+    pub id: i64,
+    pub track_id: i64,
+    pub name: String,
+    pub position: f64,
+    pub created_at: i64, // Unix ms timestamp
+}
+
+impl Bookmark {
+    pub fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            track_id: row.get("track_id")?,
+            name: row.get("name")?,
+            position: row.get("position")?,
+            created_at: row.get("created_at")?,
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Scrobbling
+// ---------------------------------------------------------------------------
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Type, PartialEq)]
+pub enum ScrobbleService {
+    LastFm,
+    ListenBrainz,
+}
+
+impl ScrobbleService {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScrobbleService::LastFm => "lastfm",
+            ScrobbleService::ListenBrainz => "listenbrainz",
+        }
+    }
+}
+
+/// A finished listen waiting to be submitted. The track details are copied so
+/// it survives the track leaving the library.
+#[derive(Clone, Serialize, Deserialize, Debug, Type)]
+pub struct PendingScrobble {
+    pub id: i64,
+    pub service: ScrobbleService,
+    pub artist: String,
+    pub album: String,
+    pub title: String,
+    pub track_number: i64,
+    pub duration: i64,
+    pub timestamp: i64, // Unix seconds, when the listen started
+    pub mbid: Option<String>,
+    pub attempts: i64,
+}
+
+impl PendingScrobble {
+    pub fn new(service: ScrobbleService, track: &FullTrack, timestamp: i64) -> Self {
+        Self {
+            id: 0,
+            service,
+            artist: track.artist_name.clone(),
+            album: track.album_title.clone(),
+            title: track.track.title.clone(),
+            track_number: track.track.track_number,
+            duration: track.track.duration,
+            timestamp,
+            mbid: track.track.musicbrainz_recording_id.clone(),
+            attempts: 0,
+        }
+    }
+
+    pub fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            service: match row.get::<_, String>("service")?.as_str() {
+                "lastfm" => ScrobbleService::LastFm,
+                _ => ScrobbleService::ListenBrainz,
+            },
+            artist: row.get("artist")?,
+            album: row.get("album")?,
+            title: row.get("title")?,
+            track_number: row.get("track_number")?,
+            duration: row.get("duration")?,
+            timestamp: row.get("timestamp")?,
+            mbid: row.get("mbid")?,
+            attempts: row.get("attempts")?,
         })
     }
 }
@@ -0,0 +1,518 @@
+//! MPRIS2 on the session bus, so desktop media controls, `playerctl` and
+//! media keys can drive the player.
+
+use std::{collections::HashMap, sync::OnceLock};
+
+use tauri::{AppHandle, Manager, Url};
+use tokio::sync::Mutex;
+use zbus::{
+    connection, fdo, interface,
+    object_server::SignalEmitter,
+    zvariant::{ObjectPath, Value},
+    Connection,
+};
+
+use crate::audio_player::{self, AudioPlayer, LoopType, ManagedPlayer, PlayerState};
+
+const BUS_NAME: &str = "org.mpris.MediaPlayer2.aurex";
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+
+static CONNECTION: OnceLock<Connection> = OnceLock::new();
+
+/// The player properties as clients last heard of them.
+static ANNOUNCED: OnceLock<Mutex<HashMap<&'static str, Value<'static>>>> = OnceLock::new();
+
+fn track_path(id: i64) -> String {
+    format!("/org/aurex/track/{id}")
+}
+
+// MPRIS talks in microseconds
+fn to_micros(seconds: f64) -> i64 {
+    (seconds * 1_000_000.0) as i64
+}
+
+fn to_seconds(micros: i64) -> f64 {
+    micros as f64 / 1_000_000.0
+}
+
+// <------------Root------------>
+struct Root {
+    app: AppHandle,
+}
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    fn raise(&self) {
+        if let Some(window) = self.app.get_webview_window("main") {
+            _ = window.unminimize();
+            _ = window.show();
+            _ = window.set_focus();
+        }
+    }
+
+    fn quit(&self) {
+        self.app.exit(0);
+    }
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> &str {
+        "Aurex"
+    }
+
+    #[zbus(property)]
+    fn desktop_entry(&self) -> &str {
+        "aurex"
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+// <------------Player------------>
+struct MprisPlayer {
+    app: AppHandle,
+}
+
+impl MprisPlayer {
+    /// Reads from the player under its lock, without cloning the queues.
+    async fn read<T>(&self, f: impl FnOnce(&AudioPlayer) -> T) -> T {
+        let player = self.app.state::<ManagedPlayer>().player.clone();
+        let player = player.lock().await;
+        f(&player)
+    }
+
+    /// The properties that follow the player, by their D-Bus names.
+    async fn properties(&self) -> HashMap<&'static str, Value<'static>> {
+        self.read(|player| {
+            HashMap::from([
+                ("PlaybackStatus", Value::from(playback_status(player))),
+                ("LoopStatus", Value::from(loop_status(player))),
+                ("Shuffle", Value::from(player.shuffle)),
+                ("Metadata", Value::from(metadata(player))),
+                ("CanGoNext", Value::from(can_go_next(player))),
+                ("CanGoPrevious", Value::from(!player.history.is_empty())),
+                ("CanPlay", Value::from(player.currently_playing.is_some())),
+                ("CanPause", Value::from(player.currently_playing.is_some())),
+                ("CanSeek", Value::from(player.currently_playing.is_some())),
+            ])
+        })
+        .await
+    }
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl MprisPlayer {
+    async fn next(&self) {
+        _ = audio_player::next(self.app.state()).await;
+    }
+
+    async fn previous(&self) {
+        _ = audio_player::previous(self.app.state()).await;
+    }
+
+    async fn pause(&self) {
+        _ = audio_player::pause(self.app.state()).await;
+    }
+
+    async fn play_pause(&self) {
+        if self.read(|p| p.state == PlayerState::Playing).await {
+            _ = audio_player::pause(self.app.state()).await;
+        } else {
+            _ = audio_player::play(self.app.state()).await;
+        }
+    }
+
+    async fn stop(&self) {
+        _ = audio_player::pause(self.app.state()).await;
+        _ = audio_player::seek(self.app.state(), 0.0).await;
+    }
+
+    async fn play(&self) {
+        _ = audio_player::play(self.app.state()).await;
+    }
+
+    async fn seek(&self, offset: i64) {
+        if let Some(progress) =
+            audio_player::current_progress(&self.app.state::<ManagedPlayer>()).await
+        {
+            let position = (progress + to_seconds(offset)).max(0.0);
+            _ = audio_player::seek(self.app.state(), position).await;
+        }
+    }
+
+    async fn set_position(&self, track_id: ObjectPath<'_>, position: i64) {
+        let Some((id, duration)) = self
+            .read(|p| {
+                p.currently_playing
+                    .as_ref()
+                    .map(|current| (current.track.id, current.track.duration))
+            })
+            .await
+        else {
+            return;
+        };
+
+        // Stale requests for a track that's no longer playing are ignored
+        if id.map(track_path).as_deref() != Some(track_id.as_str()) {
+            return;
+        }
+
+        let position = to_seconds(position);
+        if position >= 0.0 && position <= duration as f64 {
+            _ = audio_player::seek(self.app.state(), position).await;
+        }
+    }
+
+    fn open_uri(&self, _uri: String) -> fdo::Result<()> {
+        Err(fdo::Error::NotSupported(
+            "Opening URIs is not supported".into(),
+        ))
+    }
+
+    #[zbus(signal)]
+    async fn seeked(emitter: &SignalEmitter<'_>, position: i64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    async fn playback_status(&self) -> &str {
+        self.read(playback_status).await
+    }
+
+    #[zbus(property)]
+    async fn loop_status(&self) -> &str {
+        self.read(loop_status).await
+    }
+
+    #[zbus(property)]
+    async fn set_loop_status(&self, status: String) {
+        let mode = match status.as_str() {
+            "Track" => LoopType::LoopOnce,
+            "Playlist" => LoopType::LoopOver,
+            _ => LoopType::Off,
+        };
+        _ = audio_player::set_loop_mode(self.app.state(), mode).await;
+    }
+
+    /// The engine only plays at normal speed, and a minimum and maximum of
+    /// 1.0 tell clients the rate can't be changed.
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    async fn shuffle(&self) -> bool {
+        self.read(|p| p.shuffle).await
+    }
+
+    #[zbus(property)]
+    async fn set_shuffle(&self, shuffle: bool) {
+        if self.read(|p| p.shuffle).await != shuffle {
+            _ = audio_player::shuffle(self.app.state()).await;
+        }
+    }
+
+    #[zbus(property)]
+    async fn metadata(&self) -> HashMap<String, Value<'static>> {
+        self.read(metadata).await
+    }
+
+    /// The engine has no volume control yet, so this is read-only.
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property(emits_changed_signal = "false"))]
+    async fn position(&self) -> i64 {
+        audio_player::current_progress(&self.app.state::<ManagedPlayer>())
+            .await
+            .map(to_micros)
+            .unwrap_or(0)
+    }
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    async fn can_go_next(&self) -> bool {
+        self.read(can_go_next).await
+    }
+
+    #[zbus(property)]
+    async fn can_go_previous(&self) -> bool {
+        self.read(|p| !p.history.is_empty()).await
+    }
+
+    #[zbus(property)]
+    async fn can_play(&self) -> bool {
+        self.read(|p| p.currently_playing.is_some()).await
+    }
+
+    #[zbus(property)]
+    async fn can_pause(&self) -> bool {
+        self.read(|p| p.currently_playing.is_some()).await
+    }
+
+    #[zbus(property)]
+    async fn can_seek(&self) -> bool {
+        self.read(|p| p.currently_playing.is_some()).await
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+fn playback_status(player: &AudioPlayer) -> &'static str {
+    match player.state {
+        PlayerState::Playing => "Playing",
+        PlayerState::Paused => "Paused",
+        _ => "Stopped",
+    }
+}
+
+fn loop_status(player: &AudioPlayer) -> &'static str {
+    match player.looping {
+        LoopType::LoopOnce => "Track",
+        LoopType::LoopOver => "Playlist",
+        LoopType::Off => "None",
+    }
+}
+
+fn can_go_next(player: &AudioPlayer) -> bool {
+    !player.queue.is_empty() || player.looping == LoopType::LoopOver || player.autoplay
+}
+
+fn metadata(player: &AudioPlayer) -> HashMap<String, Value<'static>> {
+    let mut metadata = HashMap::new();
+
+    let Some(current) = &player.currently_playing else {
+        if let Ok(path) = ObjectPath::try_from(NO_TRACK) {
+            metadata.insert("mpris:trackid".to_string(), Value::from(path));
+        }
+        return metadata;
+    };
+
+    if let Some(path) = current
+        .track
+        .id
+        .and_then(|id| ObjectPath::try_from(track_path(id)).ok())
+    {
+        metadata.insert("mpris:trackid".to_string(), Value::from(path));
+    }
+
+    metadata.insert(
+        "mpris:length".to_string(),
+        Value::from(to_micros(current.track.duration as f64)),
+    );
+    metadata.insert(
+        "xesam:title".to_string(),
+        Value::from(current.track.title.clone()),
+    );
+    metadata.insert(
+        "xesam:artist".to_string(),
+        Value::from(vec![current.artist_name.clone()]),
+    );
+    metadata.insert(
+        "xesam:album".to_string(),
+        Value::from(current.album_title.clone()),
+    );
+    metadata.insert(
+        "xesam:trackNumber".to_string(),
+        Value::from(current.track.track_number as i32),
+    );
+    metadata.insert(
+        "xesam:discNumber".to_string(),
+        Value::from(current.track.disc_number as i32),
+    );
+    metadata.insert(
+        "xesam:url".to_string(),
+        Value::from(file_url(current.track.audio_path())),
+    );
+
+    if let Some(composer) = current.track.composer.clone() {
+        metadata.insert("xesam:composer".to_string(), Value::from(vec![composer]));
+    }
+    if let Some(genre) = current.genre.clone() {
+        metadata.insert("xesam:genre".to_string(), Value::from(vec![genre]));
+    }
+    if let Some(album_art) = &current.album_art {
+        metadata.insert("mpris:artUrl".to_string(), Value::from(file_url(album_art)));
+    }
+
+    metadata
+}
+
+fn file_url(path: &str) -> String {
+    if path.contains("://") {
+        return path.to_string();
+    }
+
+    Url::from_file_path(path)
+        .map(String::from)
+        .unwrap_or_default()
+}
+
+// <------------Service------------>
+/// Claims the MPRIS bus name. Failing to reach the session bus only costs the
+/// desktop integration, so it's logged and otherwise ignored.
+pub fn start_mpris(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let connection = connection::Builder::session()
+            .and_then(|b| b.name(BUS_NAME))
+            .and_then(|b| {
+                b.serve_at(
+                    OBJECT_PATH,
+                    Root {
+                        app: app_handle.clone(),
+                    },
+                )
+            })
+            .and_then(|b| {
+                b.serve_at(
+                    OBJECT_PATH,
+                    MprisPlayer {
+                        app: app_handle.clone(),
+                    },
+                )
+            });
+
+        let connection = match connection {
+            Ok(builder) => builder.build().await,
+            Err(e) => Err(e),
+        };
+
+        match connection {
+            Ok(connection) => {
+                _ = CONNECTION.set(connection);
+            }
+            Err(e) => eprintln!("Failed to start MPRIS: {}", e),
+        }
+    });
+}
+
+/// Announces the new player state to MPRIS clients, in one signal holding
+/// only the properties that changed.
+pub fn player_changed() {
+    let Some(connection) = CONNECTION.get() else {
+        return;
+    };
+
+    let connection = connection.clone();
+    tauri::async_runtime::spawn(async move {
+        let Ok(iface_ref) = connection
+            .object_server()
+            .interface::<_, MprisPlayer>(OBJECT_PATH)
+            .await
+        else {
+            return;
+        };
+
+        // Held until the signal is out, so updates are announced in order
+        let mut announced = ANNOUNCED.get_or_init(Default::default).lock().await;
+        let current = iface_ref.get().await.properties().await;
+
+        {
+            let changed: HashMap<&str, &Value> = current
+                .iter()
+                .filter(|(name, value)| announced.get(*name) != Some(*value))
+                .map(|(name, value)| (*name, value))
+                .collect();
+
+            if !changed.is_empty() {
+                _ = iface_ref
+                    .signal_emitter()
+                    .emit(
+                        PROPERTIES_INTERFACE,
+                        "PropertiesChanged",
+                        &(PLAYER_INTERFACE, changed, Vec::<&str>::new()),
+                    )
+                    .await;
+            }
+        }
+
+        *announced = current;
+    });
+}
+
+/// Tells MPRIS clients the position jumped, they extrapolate it otherwise.
+pub fn seeked(position: f64) {
+    let Some(connection) = CONNECTION.get() else {
+        return;
+    };
+
+    let connection = connection.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Ok(iface_ref) = connection
+            .object_server()
+            .interface::<_, MprisPlayer>(OBJECT_PATH)
+            .await
+        {
+            _ = MprisPlayer::seeked(iface_ref.signal_emitter(), to_micros(position)).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_urls_are_escaped() {
+        assert_eq!(
+            file_url("/music/Kind of Blue/01 So What #1.flac"),
+            "file:///music/Kind%20of%20Blue/01%20So%20What%20%231.flac"
+        );
+        assert_eq!(
+            file_url("https://example.com/a.jpg"),
+            "https://example.com/a.jpg"
+        );
+        assert_eq!(file_url("relative/cover.jpg"), "");
+    }
+
+    #[test]
+    fn positions_convert_to_microseconds_and_back() {
+        assert_eq!(to_micros(1.5), 1_500_000);
+        assert_eq!(to_seconds(2_250_000), 2.25);
+        assert_eq!(to_seconds(to_micros(93.0)), 93.0);
+    }
+
+    #[test]
+    fn track_ids_are_object_paths() {
+        assert!(ObjectPath::try_from(track_path(42)).is_ok());
+        assert!(ObjectPath::try_from(NO_TRACK).is_ok());
+    }
+}
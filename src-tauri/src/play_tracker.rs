@@ -0,0 +1,198 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tauri::Emitter;
+
+use crate::{
+    audio_player::ManagedPlayer,
+    library_service::library_service,
+    models::FullTrack,
+    scrobble_log::{self, Rating},
+    scrobbler,
+};
+
+/// Tracks shorter than this are never counted.
+const MIN_TRACK_LENGTH: f64 = 30.0;
+/// Listening this long always counts, however long the track is.
+const MAX_THRESHOLD: f64 = 240.0;
+/// Progress jumps bigger than this are seeks, not listening. Leaves room for
+/// the slowest progress interval and a late tick.
+const MAX_PROGRESS_STEP: f64 = 2.0;
+
+/// The track currently being listened to and how much of it was actually heard.
+#[derive(Clone, Debug)]
+pub struct Listen {
+    pub track: FullTrack,
+    pub started_at: i64, // Unix ms timestamp
+    pub listened: f64,
+    pub last_progress: f64,
+    pub counted: bool,
+}
+
+impl Listen {
+    /// Half the track or four minutes, whichever comes first.
+    fn threshold(&self) -> Option<f64> {
+        let duration = self.track.track.duration as f64;
+        if duration < MIN_TRACK_LENGTH {
+            return None;
+        }
+        Some((duration / 2.0).min(MAX_THRESHOLD))
+    }
+
+    /// Adds whatever was heard since the last tick. True the first time the
+    /// listen crosses the threshold.
+    fn heard(&mut self, progress: f64) -> bool {
+        let step = progress - self.last_progress;
+        if step > 0.0 && step <= MAX_PROGRESS_STEP {
+            self.listened += step;
+        }
+        self.last_progress = progress;
+
+        if self.counted {
+            return false;
+        }
+
+        self.counted = self.threshold().is_some_and(|t| self.listened >= t);
+        self.counted
+    }
+}
+
+pub fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+pub async fn start(state: &ManagedPlayer, track: &FullTrack, position: f64) {
+    *state.listen.lock().await = Some(Listen {
+        track: track.clone(),
+        started_at: now_ms(),
+        listened: 0.0,
+        last_progress: position,
+        counted: false,
+    });
+}
+
+/// Adds whatever was heard since the last tick and records a play once the
+/// threshold is crossed.
+pub async fn progress(state: &ManagedPlayer, progress: f64) {
+    let mut listen = state.listen.lock().await;
+    let Some(current) = listen.as_mut() else {
+        return;
+    };
+
+    if current.heard(progress) {
+        let counted = current.clone();
+        drop(listen);
+
+        played(state, &counted).await;
+    }
+}
+
+/// Called when the user moves on from a track before it counted as played.
+pub async fn skipped(state: &ManagedPlayer) {
+    let listen = state.listen.lock().await.take();
+
+    let Some(listen) = listen.filter(|l| !l.counted) else {
+        return;
+    };
+
+    if let (Ok(library), Some(id)) = (library_service().lock(), listen.track.track.id) {
+        _ = library.record_skip(id, now_ms());
+    }
+
+    if let Err(e) = scrobble_log::append(&listen.track, Rating::Skipped, listen.started_at) {
+        eprintln!("Failed to write scrobble log: {}", e);
+    }
+}
+
+async fn played(state: &ManagedPlayer, listen: &Listen) {
+    let Some(id) = listen.track.track.id else {
+        return;
+    };
+
+    if let Ok(library) = library_service().lock() {
+        if let Err(e) = library.record_play(id, listen.started_at) {
+            eprintln!("Failed to record play: {}", e);
+            return;
+        }
+    }
+
+    if let Err(e) = scrobble_log::append(&listen.track, Rating::Listened, listen.started_at) {
+        eprintln!("Failed to write scrobble log: {}", e);
+    }
+
+    scrobbler::scrobble(&state.app, &listen.track, listen.started_at).await;
+
+    _ = state.app.emit("track-played", id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listen(duration: i64) -> Listen {
+        let mut track = FullTrack::stub(1, 1);
+        track.track.duration = duration;
+        Listen {
+            track,
+            started_at: 0,
+            listened: 0.0,
+            last_progress: 0.0,
+            counted: false,
+        }
+    }
+
+    /// Plays from the current position to `until` in one second ticks.
+    fn play_to(listen: &mut Listen, until: f64) -> usize {
+        let mut crossed = 0;
+        while listen.last_progress < until {
+            if listen.heard((listen.last_progress + 1.0).min(until)) {
+                crossed += 1;
+            }
+        }
+        crossed
+    }
+
+    #[test]
+    fn threshold_is_half_the_track_up_to_four_minutes() {
+        assert_eq!(listen(29).threshold(), None);
+        assert_eq!(listen(30).threshold(), Some(15.0));
+        assert_eq!(listen(200).threshold(), Some(100.0));
+        assert_eq!(listen(3600).threshold(), Some(MAX_THRESHOLD));
+    }
+
+    #[test]
+    fn counts_once_when_threshold_is_crossed() {
+        let mut listen = listen(200);
+
+        assert_eq!(play_to(&mut listen, 99.0), 0);
+        assert!(!listen.counted);
+        assert_eq!(play_to(&mut listen, 200.0), 1);
+        assert!(listen.counted);
+        assert_eq!(listen.listened, 200.0);
+    }
+
+    #[test]
+    fn never_counts_short_tracks() {
+        let mut listen = listen(20);
+
+        assert_eq!(play_to(&mut listen, 20.0), 0);
+        assert_eq!(listen.listened, 20.0);
+    }
+
+    #[test]
+    fn seeks_are_not_listening() {
+        let mut listen = listen(200);
+
+        play_to(&mut listen, 10.0);
+        // Forward past the threshold, then back to the start
+        assert!(!listen.heard(150.0));
+        assert!(!listen.heard(0.0));
+        assert_eq!(listen.listened, 10.0);
+
+        // Ticks up to the largest step still count
+        assert!(!listen.heard(MAX_PROGRESS_STEP));
+        assert_eq!(listen.listened, 10.0 + MAX_PROGRESS_STEP);
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::HashSet;
+
+use rand::rng;
+use rand::seq::SliceRandom;
+
+use crate::{library_service::library_service, models::FullTrack};
+
+/// How many tracks get queued each time the queue runs dry.
+const BATCH_SIZE: usize = 10;
+/// Picks are drawn at random from this many of the best matches.
+const POOL_SIZE: usize = 40;
+/// Anything scoring below this isn't similar enough to play.
+const MIN_SCORE: u32 = 3;
+
+/// Tracks similar to `seed`, in random order, leaving out everything in `recent`.
+pub fn similar_tracks(seed: &FullTrack, recent: &HashSet<i64>) -> Vec<FullTrack> {
+    let tracks = match library_service().lock() {
+        Ok(library) => library.get_all_tracks().unwrap_or_default(),
+        Err(_) => return Vec::new(),
+    };
+
+    let mut scored: Vec<(FullTrack, u32)> = tracks
+        .into_iter()
+        .filter(|t| t.track.id.is_some_and(|id| !recent.contains(&id)))
+        .map(|t| {
+            let score = similarity(seed, &t);
+            (t, score)
+        })
+        .filter(|(_, score)| *score >= MIN_SCORE)
+        .collect();
+
+    scored.sort_by(|(_, a), (_, b)| b.cmp(a));
+    scored.truncate(POOL_SIZE);
+
+    let mut pool: Vec<FullTrack> = scored.into_iter().map(|(t, _)| t).collect();
+    pool.shuffle(&mut rng());
+    pool.truncate(BATCH_SIZE);
+    pool
+}
+
+fn similarity(seed: &FullTrack, other: &FullTrack) -> u32 {
+    let mut score = 0;
+
+    if let (Some(a), Some(b)) = (&seed.genre, &other.genre) {
+        if a.eq_ignore_ascii_case(b) {
+            score += 4;
+        }
+    }
+
+    if seed.track.artist_id == other.track.artist_id {
+        score += 3;
+    }
+
+    if let (Some(a), Some(b)) = (&seed.track.composer, &other.track.composer) {
+        if a.eq_ignore_ascii_case(b) {
+            score += 2;
+        }
+    }
+
+    let (bpm_a, bpm_b) = (seed.track.bpm, other.track.bpm);
+    if bpm_a > 0 && bpm_b > 0 && (bpm_a - bpm_b).abs() * 100 <= bpm_a * 6 {
+        score += 1;
+    }
+
+    if let (Some(a), Some(b)) = (
+        seed.track.initial_key.as_deref().and_then(camelot),
+        other.track.initial_key.as_deref().and_then(camelot),
+    ) {
+        if keys_compatible(a, b) {
+            score += 1;
+        }
+    }
+
+    if seed.year > 0 && other.year > 0 && (seed.year - other.year).abs() <= 5 {
+        score += 1;
+    }
+
+    score
+}
+
+/// Position on the Camelot wheel: (1..=12, minor).
+type Camelot = (u8, bool);
+
+/// Same number, or a step around the wheel without switching major/minor.
+fn keys_compatible(a: Camelot, b: Camelot) -> bool {
+    let step = (a.0 as i8 - b.0 as i8).rem_euclid(12);
+    a.0 == b.0 || (a.1 == b.1 && (step == 1 || step == 11))
+}
+
+/// Reads Camelot ("8A"), Open Key ("1m") and plain notation ("Am", "F# minor").
+fn camelot(key: &str) -> Option<Camelot> {
+    let key = key.trim();
+    let digits: String = key.chars().take_while(|c| c.is_ascii_digit()).collect();
+
+    if !digits.is_empty() {
+        let number = digits.parse::<u8>().ok().filter(|n| (1..=12).contains(n))?;
+
+        return match key[digits.len()..].trim() {
+            "A" | "a" => Some((number, true)),
+            "B" | "b" => Some((number, false)),
+            // Open Key 1 sits at Camelot 8
+            "m" | "M" => Some(((number + 6) % 12 + 1, true)),
+            "d" | "D" => Some(((number + 6) % 12 + 1, false)),
+            _ => None,
+        };
+    }
+
+    let mut chars = key.chars();
+    let mut pitch: i32 = match chars.next()?.to_ascii_uppercase() {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return None,
+    };
+
+    let rest: String = chars.collect();
+    let mut rest = rest.as_str();
+
+    if let Some(r) = rest.strip_prefix(['#', '♯']) {
+        pitch += 1;
+        rest = r;
+    } else if let Some(r) = rest.strip_prefix(['b', '♭']) {
+        pitch -= 1;
+        rest = r;
+    }
+
+    let rest = rest.trim().to_lowercase();
+    let minor = rest.starts_with('m') && !rest.starts_with("maj");
+
+    // Minor keys share a number with their relative major
+    let major_pitch = if minor { pitch + 3 } else { pitch };
+    let number = (7 * major_pitch.rem_euclid(12) + 7) % 12 + 1;
+
+    Some((number as u8, minor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn camelot_reads_camelot_notation() {
+        assert_eq!(camelot("8A"), Some((8, true)));
+        assert_eq!(camelot(" 12b "), Some((12, false)));
+        assert_eq!(camelot("13A"), None);
+        assert_eq!(camelot("0B"), None);
+        assert_eq!(camelot("8X"), None);
+    }
+
+    #[test]
+    fn camelot_reads_open_key() {
+        assert_eq!(camelot("1m"), Some((8, true)));
+        assert_eq!(camelot("1d"), Some((8, false)));
+        assert_eq!(camelot("6m"), Some((1, true)));
+        assert_eq!(camelot("12d"), Some((7, false)));
+    }
+
+    #[test]
+    fn camelot_reads_plain_notation() {
+        assert_eq!(camelot("C"), Some((8, false)));
+        assert_eq!(camelot("Am"), Some((8, true)));
+        assert_eq!(camelot("G"), Some((9, false)));
+        assert_eq!(camelot("F# minor"), Some((11, true)));
+        assert_eq!(camelot("Db"), Some((3, false)));
+        assert_eq!(camelot("Bb"), Some((6, false)));
+        assert_eq!(camelot("E♭m"), Some((2, true)));
+        assert_eq!(camelot("Cmaj"), Some((8, false)));
+        assert_eq!(camelot("H"), None);
+        assert_eq!(camelot(""), None);
+    }
+
+    #[test]
+    fn compatible_keys_are_neighbours_on_the_wheel() {
+        assert!(keys_compatible((8, true), (8, true)));
+        assert!(keys_compatible((8, true), (8, false)));
+        assert!(keys_compatible((8, true), (9, true)));
+        assert!(keys_compatible((12, false), (1, false)));
+        assert!(!keys_compatible((8, true), (9, false)));
+        assert!(!keys_compatible((8, true), (10, true)));
+    }
+}
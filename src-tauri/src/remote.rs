@@ -0,0 +1,467 @@
+//! Opt-in HTTP API for scripts, Stream Deck setups and phones, with the
+//! player events pushed over a WebSocket.
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, Query, Request, State,
+    },
+    http::{header::AUTHORIZATION, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tauri::{AppHandle, Listener, Manager};
+use tokio::sync::{
+    broadcast::{self, error::RecvError},
+    oneshot, watch, Mutex,
+};
+
+use crate::{
+    audio_player::{self, AudioPlayer, LoopType, ManagedPlayer, QueueEntry},
+    error::{CommandError, CommandResult},
+    library_service::library_service,
+    media_lib_cmd,
+    models::{Album, Artist, FullTrack, Playlist, SearchResults, Track},
+    settings::{ManagedSettings, Settings},
+};
+
+/// Events forwarded to WebSocket clients.
+const FORWARDED_EVENTS: [&str; 3] = ["player-changed", "queue-changed", "progress-changed"];
+
+#[derive(Clone, Serialize, Deserialize, Debug, Type)]
+#[serde(default)]
+pub struct RemoteSettings {
+    pub enabled: bool,
+    /// Only reachable from this machine unless changed
+    pub bind: String,
+    pub port: u16,
+    /// Sent as `Authorization: Bearer <token>`, or `?token=` for WebSockets
+    pub token: String,
+}
+
+impl Default for RemoteSettings {
+    fn default() -> Self {
+        RemoteSettings {
+            enabled: false,
+            bind: "127.0.0.1".to_string(),
+            port: 7878,
+            token: String::new(),
+        }
+    }
+}
+
+fn generate_token() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+// <------------Commands------------>
+#[tauri::command]
+#[specta::specta]
+pub async fn set_remote_control(
+    app_handle: AppHandle,
+    state: tauri::State<'_, ManagedSettings>,
+    enabled: bool,
+    bind: Option<String>,
+    port: Option<u16>,
+) -> CommandResult<Settings> {
+    state
+        .update(|s| {
+            s.remote.enabled = enabled;
+            if let Some(bind) = bind {
+                s.remote.bind = bind;
+            }
+            if let Some(port) = port {
+                s.remote.port = port;
+            }
+            if s.remote.token.is_empty() {
+                s.remote.token = generate_token();
+            }
+        })
+        .await;
+
+    restart_remote(&app_handle).await?;
+
+    Ok(state.public().await)
+}
+
+/// Locks out every client that knew the old token, including open WebSockets.
+#[tauri::command]
+#[specta::specta]
+pub async fn regenerate_remote_token(
+    state: tauri::State<'_, ManagedSettings>,
+    remote: tauri::State<'_, ManagedRemote>,
+) -> CommandResult<Settings> {
+    state
+        .update(|s| {
+            s.remote.token = generate_token();
+        })
+        .await;
+
+    remote.disconnect_clients();
+
+    Ok(state.public().await)
+}
+
+/// The token itself, which settings only ever hand out redacted.
+#[tauri::command]
+#[specta::specta]
+pub async fn get_remote_token(state: tauri::State<'_, ManagedSettings>) -> CommandResult<String> {
+    Ok(state.get().await.remote.token)
+}
+
+// <------------Server------------>
+pub struct ManagedRemote {
+    shutdown: Mutex<Option<oneshot::Sender<()>>>,
+    events: broadcast::Sender<String>,
+    /// Bumped to close every open WebSocket, they outlive the server's shutdown
+    disconnect: watch::Sender<()>,
+}
+
+impl ManagedRemote {
+    fn disconnect_clients(&self) {
+        self.disconnect.send_replace(());
+    }
+}
+
+/// Starts forwarding events and brings the server up if it was left enabled.
+pub fn init_remote(app_handle: AppHandle) {
+    let (events, _) = broadcast::channel(64);
+
+    for name in FORWARDED_EVENTS {
+        let events = events.clone();
+        app_handle.listen_any(name, move |event| {
+            // Payloads are already JSON
+            _ = events.send(format!(
+                r#"{{"event":"{}","payload":{}}}"#,
+                name,
+                event.payload()
+            ));
+        });
+    }
+
+    app_handle.manage(ManagedRemote {
+        shutdown: Mutex::new(None),
+        events,
+        disconnect: watch::channel(()).0,
+    });
+
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = restart_remote(&app_handle).await {
+            eprintln!("Failed to start remote control: {}", e);
+        }
+    });
+}
+
+/// Stops the running server, if any, and starts it again with the current settings.
+async fn restart_remote(app_handle: &AppHandle) -> CommandResult<()> {
+    let remote = app_handle.state::<ManagedRemote>();
+    let mut shutdown = remote.shutdown.lock().await;
+
+    if let Some(stop) = shutdown.take() {
+        _ = stop.send(());
+        remote.disconnect_clients();
+    }
+
+    let settings = app_handle.state::<ManagedSettings>().get().await.remote;
+    if !settings.enabled {
+        return Ok(());
+    }
+
+    let listener = tokio::net::TcpListener::bind((settings.bind.as_str(), settings.port)).await?;
+
+    let (stop, stopped) = oneshot::channel();
+    *shutdown = Some(stop);
+
+    let router = router(app_handle.clone());
+    tauri::async_runtime::spawn(async move {
+        let server = axum::serve(listener, router).with_graceful_shutdown(async {
+            _ = stopped.await;
+        });
+
+        if let Err(e) = server.await {
+            eprintln!("Remote control server stopped: {}", e);
+        }
+    });
+
+    Ok(())
+}
+
+fn router(app_handle: AppHandle) -> Router {
+    Router::new()
+        .route("/player", get(get_player))
+        .route("/player/play", post(play))
+        .route("/player/pause", post(pause))
+        .route("/player/next", post(next))
+        .route("/player/previous", post(previous))
+        .route("/player/seek", post(seek))
+        .route("/player/shuffle", post(shuffle))
+        .route("/player/loop", post(set_loop_mode))
+        .route("/queue", get(get_queue).post(add_to_queue))
+        .route("/queue/next", post(play_next))
+        .route("/queue/{entry_id}", delete(remove_from_queue))
+        .route("/queue/{entry_id}/move", post(move_entry))
+        .route("/queue/{entry_id}/skip", post(skip_to_entry))
+        .route("/library/tracks", get(get_all_tracks))
+        .route("/library/albums", get(get_all_albums))
+        .route("/library/albums/{id}/tracks", get(get_album_tracks))
+        .route("/library/artists", get(get_all_artists))
+        .route("/library/artists/{id}/albums", get(get_artist_albums))
+        .route("/library/playlists", get(get_all_playlists))
+        .route("/library/playlists/{id}/tracks", get(get_playlist_tracks))
+        .route("/library/search", get(search))
+        .route("/events", get(events))
+        .layer(middleware::from_fn_with_state(
+            app_handle.clone(),
+            authorize,
+        ))
+        .with_state(app_handle)
+}
+
+async fn authorize(State(app_handle): State<AppHandle>, request: Request, next: Next) -> Response {
+    let token = app_handle
+        .state::<ManagedSettings>()
+        .get()
+        .await
+        .remote
+        .token;
+
+    let from_header = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let from_query = request.uri().query().and_then(|query| {
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("token="))
+    });
+
+    let authorized = from_header
+        .or(from_query)
+        .is_some_and(|provided| !token.is_empty() && tokens_match(provided, &token));
+
+    if authorized {
+        next.run(request).await
+    } else {
+        StatusCode::UNAUTHORIZED.into_response()
+    }
+}
+
+// Takes the same time whichever byte differs
+fn tokens_match(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
+
+// <------------Player------------>
+type ApiResult<T> = Result<Json<T>, CommandError>;
+
+// Same `{ kind, message }` body the frontend gets
+impl IntoResponse for CommandError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            CommandError::NotFound(_) => StatusCode::NOT_FOUND,
+            CommandError::InvalidIndex(_) | CommandError::InvalidArgument(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        (status, Json(self)).into_response()
+    }
+}
+
+fn reply<T>(result: CommandResult<T>) -> ApiResult<T> {
+    result.map(Json)
+}
+
+fn full_track(track_id: i64) -> CommandResult<FullTrack> {
+    library_service()
+        .lock()?
+        .get_full_track_by_id(track_id)?
+        .ok_or_else(|| CommandError::not_found(format!("Track {track_id}")))
+}
+
+#[derive(Deserialize)]
+struct SeekBody {
+    time: f64,
+}
+
+#[derive(Deserialize)]
+struct LoopBody {
+    mode: LoopType,
+}
+
+#[derive(Deserialize)]
+struct TrackBody {
+    track_id: i64,
+}
+
+#[derive(Deserialize)]
+struct MoveBody {
+    index: i32,
+}
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    q: String,
+}
+
+async fn get_player(State(app_handle): State<AppHandle>) -> ApiResult<AudioPlayer> {
+    reply(audio_player::get_player(app_handle.state()).await)
+}
+
+async fn play(State(app_handle): State<AppHandle>) -> ApiResult<AudioPlayer> {
+    reply(audio_player::play(app_handle.state()).await)
+}
+
+async fn pause(State(app_handle): State<AppHandle>) -> ApiResult<AudioPlayer> {
+    reply(audio_player::pause(app_handle.state()).await)
+}
+
+async fn next(State(app_handle): State<AppHandle>) -> ApiResult<AudioPlayer> {
+    reply(audio_player::next(app_handle.state()).await)
+}
+
+async fn previous(State(app_handle): State<AppHandle>) -> ApiResult<AudioPlayer> {
+    reply(audio_player::previous(app_handle.state()).await)
+}
+
+async fn seek(
+    State(app_handle): State<AppHandle>,
+    Json(body): Json<SeekBody>,
+) -> ApiResult<AudioPlayer> {
+    audio_player::seek(app_handle.state(), body.time).await?;
+    reply(audio_player::get_player(app_handle.state()).await)
+}
+
+async fn shuffle(State(app_handle): State<AppHandle>) -> ApiResult<AudioPlayer> {
+    reply(audio_player::shuffle(app_handle.state()).await)
+}
+
+async fn set_loop_mode(
+    State(app_handle): State<AppHandle>,
+    Json(body): Json<LoopBody>,
+) -> ApiResult<AudioPlayer> {
+    reply(audio_player::set_loop_mode(app_handle.state(), body.mode).await)
+}
+
+// <------------Queue------------>
+async fn get_queue(State(app_handle): State<AppHandle>) -> Json<Vec<QueueEntry>> {
+    let player = app_handle.state::<ManagedPlayer>().get().await;
+    Json(player.queue.into())
+}
+
+async fn add_to_queue(
+    State(app_handle): State<AppHandle>,
+    Json(body): Json<TrackBody>,
+) -> ApiResult<AudioPlayer> {
+    let track = full_track(body.track_id)?;
+    reply(audio_player::add_to_queue(app_handle.state(), track).await)
+}
+
+async fn play_next(
+    State(app_handle): State<AppHandle>,
+    Json(body): Json<TrackBody>,
+) -> ApiResult<AudioPlayer> {
+    let track = full_track(body.track_id)?;
+    reply(audio_player::play_next(app_handle.state(), track).await)
+}
+
+async fn remove_from_queue(
+    State(app_handle): State<AppHandle>,
+    Path(entry_id): Path<u32>,
+) -> ApiResult<AudioPlayer> {
+    reply(audio_player::remove_from_queue(app_handle.state(), entry_id).await)
+}
+
+async fn move_entry(
+    State(app_handle): State<AppHandle>,
+    Path(entry_id): Path<u32>,
+    Json(body): Json<MoveBody>,
+) -> ApiResult<AudioPlayer> {
+    reply(audio_player::change_queue_index(app_handle.state(), entry_id, body.index).await)
+}
+
+async fn skip_to_entry(
+    State(app_handle): State<AppHandle>,
+    Path(entry_id): Path<u32>,
+) -> ApiResult<AudioPlayer> {
+    reply(audio_player::skip_to_entry(app_handle.state(), entry_id).await)
+}
+
+// <------------Library------------>
+async fn get_all_tracks() -> ApiResult<Vec<FullTrack>> {
+    reply(media_lib_cmd::get_all_tracks().await)
+}
+
+async fn get_all_albums() -> ApiResult<Vec<Album>> {
+    reply(media_lib_cmd::get_all_albums().await)
+}
+
+async fn get_album_tracks(Path(id): Path<i32>) -> ApiResult<Vec<Track>> {
+    reply(media_lib_cmd::get_album_tracks(id).await)
+}
+
+async fn get_all_artists() -> ApiResult<Vec<Artist>> {
+    reply(media_lib_cmd::get_all_artists().await)
+}
+
+async fn get_artist_albums(Path(id): Path<i32>) -> ApiResult<Vec<Album>> {
+    reply(media_lib_cmd::get_artist_albums(id).await)
+}
+
+async fn get_all_playlists() -> ApiResult<Vec<Playlist>> {
+    reply(media_lib_cmd::get_all_playlists().await)
+}
+
+async fn get_playlist_tracks(Path(id): Path<i32>) -> ApiResult<Vec<FullTrack>> {
+    reply(media_lib_cmd::get_playlist_tracks(id).await)
+}
+
+async fn search(Query(query): Query<SearchQuery>) -> ApiResult<SearchResults> {
+    reply(media_lib_cmd::search(query.q).await)
+}
+
+// <------------Events------------>
+async fn events(State(app_handle): State<AppHandle>, ws: WebSocketUpgrade) -> Response {
+    let remote = app_handle.state::<ManagedRemote>();
+    let receiver = remote.events.subscribe();
+    let disconnect = remote.disconnect.subscribe();
+    ws.on_upgrade(move |socket| forward_events(socket, receiver, disconnect))
+}
+
+async fn forward_events(
+    mut socket: WebSocket,
+    mut receiver: broadcast::Receiver<String>,
+    mut disconnect: watch::Receiver<()>,
+) {
+    loop {
+        let event = tokio::select! {
+            event = receiver.recv() => event,
+            // The token changed or the server went down
+            _ = disconnect.changed() => {
+                _ = socket.send(Message::Close(None)).await;
+                break;
+            }
+        };
+
+        match event {
+            Ok(event) => {
+                if socket.send(Message::Text(event.into())).await.is_err() {
+                    break;
+                }
+            }
+            // A slow client just misses some progress updates
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! ReplayGain and R128 loudness tags, read when a file is indexed. The pinned
+//! libaurex can't scale its output yet, so the gain isn't applied on playback.
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::models::Track;
+
+/// R128 gains are relative to -23 LUFS, ReplayGain to roughly -18 LUFS.
+const R128_TO_REPLAYGAIN: f64 = 5.0;
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Type, PartialEq, Default)]
+#[allow(dead_code)] // Picked by the user once the engine can apply a gain
+pub enum ReplayGainMode {
+    #[default]
+    Off,
+    Track,
+    Album,
+    /// Album gain while an album is being played front to back, track gain otherwise
+    Auto,
+}
+
+/// Parses a ReplayGain value such as "-6.54 dB" or "0.988547".
+pub fn parse_value(value: &str) -> Option<f64> {
+    value
+        .trim()
+        .trim_end_matches("dB")
+        .trim_end_matches("db")
+        .trim()
+        .parse::<f64>()
+        .ok()
+}
+
+/// Parses an R128 gain (a Q7.8 fixed point integer) into ReplayGain dB.
+pub fn parse_r128(value: &str) -> Option<f64> {
+    let raw = value.trim().parse::<i32>().ok()?;
+    Some(raw as f64 / 256.0 + R128_TO_REPLAYGAIN)
+}
+
+/// The linear gain to apply for `track`, pulled down where needed so its
+/// peak doesn't clip.
+#[allow(dead_code)] // Applied once the engine can set its output gain
+pub fn gain_for(track: &Track, mode: ReplayGainMode, album_in_order: bool) -> f64 {
+    let use_album = match mode {
+        ReplayGainMode::Off => return 1.0,
+        ReplayGainMode::Track => false,
+        ReplayGainMode::Album => true,
+        ReplayGainMode::Auto => album_in_order,
+    };
+
+    let album = track.album_gain.map(|g| (g, track.album_peak));
+    let single = track.track_gain.map(|g| (g, track.track_peak));
+
+    let picked = if use_album {
+        album.or(single)
+    } else {
+        single.or(album)
+    };
+
+    let Some((gain_db, peak)) = picked else {
+        return 1.0;
+    };
+
+    let gain = 10f64.powf(gain_db / 20.0);
+
+    match peak {
+        Some(peak) if peak > 0.0 && gain * peak > 1.0 => 1.0 / peak,
+        _ => gain,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::FullTrack;
+
+    fn track(track_gain: Option<f64>, album_gain: Option<f64>) -> Track {
+        let mut track = FullTrack::stub(1, 1).track;
+        track.track_gain = track_gain;
+        track.album_gain = album_gain;
+        track
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{a} != {b}");
+    }
+
+    #[test]
+    fn parses_values_with_and_without_unit() {
+        assert_eq!(parse_value("-6.54 dB"), Some(-6.54));
+        assert_eq!(parse_value(" +2.1 db "), Some(2.1));
+        assert_eq!(parse_value("0.988547"), Some(0.988547));
+        assert_eq!(parse_value("loud"), None);
+    }
+
+    #[test]
+    fn parses_r128_relative_to_replaygain() {
+        assert_eq!(parse_r128("0"), Some(5.0));
+        assert_eq!(parse_r128("-512"), Some(3.0));
+        assert_eq!(parse_r128("1.5"), None);
+    }
+
+    #[test]
+    fn off_leaves_the_volume_alone() {
+        assert_eq!(
+            gain_for(&track(Some(-6.0), Some(-3.0)), ReplayGainMode::Off, true),
+            1.0
+        );
+    }
+
+    #[test]
+    fn picks_the_gain_for_the_mode() {
+        let t = track(Some(-20.0), Some(-6.0));
+
+        assert_close(gain_for(&t, ReplayGainMode::Track, true), 0.1);
+        assert_close(gain_for(&t, ReplayGainMode::Album, false), 10f64.powf(-0.3));
+        assert_close(gain_for(&t, ReplayGainMode::Auto, true), 10f64.powf(-0.3));
+        assert_close(gain_for(&t, ReplayGainMode::Auto, false), 0.1);
+    }
+
+    #[test]
+    fn falls_back_to_the_other_gain() {
+        assert_close(
+            gain_for(&track(None, Some(-20.0)), ReplayGainMode::Track, false),
+            0.1,
+        );
+        assert_close(
+            gain_for(&track(Some(-20.0), None), ReplayGainMode::Album, true),
+            0.1,
+        );
+        assert_eq!(
+            gain_for(&track(None, None), ReplayGainMode::Album, true),
+            1.0
+        );
+    }
+
+    #[test]
+    fn peak_keeps_the_gain_from_clipping() {
+        let mut t = track(Some(6.0), None);
+        t.track_peak = Some(0.8);
+        assert_close(gain_for(&t, ReplayGainMode::Track, false), 1.25);
+
+        // Quiet enough already, the peak doesn't matter
+        t.track_gain = Some(-6.0);
+        assert_close(gain_for(&t, ReplayGainMode::Track, false), 10f64.powf(-0.3));
+    }
+}
@@ -0,0 +1,75 @@
+//! Offline scrobble log in the Rockbox `.scrobbler.log` format, for uploading
+//! with existing tools.
+
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+};
+
+use crate::{constants::scrobbler_log, models::FullTrack};
+
+const HEADER: &str = concat!(
+    "#AUDIOSCROBBLER/1.1\n",
+    "#TZ/UTC\n",
+    "#CLIENT/Aurex ",
+    env!("CARGO_PKG_VERSION"),
+    "\n"
+);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rating {
+    /// Listened past the scrobble threshold
+    Listened,
+    Skipped,
+}
+
+impl Rating {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Rating::Listened => "L",
+            Rating::Skipped => "S",
+        }
+    }
+}
+
+// Fields are tab separated, so tabs and newlines can't appear inside them
+fn field(value: &str) -> String {
+    value.replace(['\t', '\n', '\r'], " ")
+}
+
+/// Appends one entry, writing the header first if the log is new.
+/// `started_at` is a Unix ms timestamp.
+pub fn append(track: &FullTrack, rating: Rating, started_at: i64) -> io::Result<()> {
+    let path = scrobbler_log();
+    let is_new = !path.exists();
+
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+
+    if is_new {
+        file.write_all(HEADER.as_bytes())?;
+    }
+
+    let track_number = match track.track.track_number {
+        n if n > 0 => n.to_string(),
+        _ => String::new(),
+    };
+
+    let entry = [
+        field(&track.artist_name),
+        field(&track.album_title),
+        field(&track.track.title),
+        track_number,
+        track.track.duration.to_string(),
+        rating.as_str().to_string(),
+        (started_at / 1000).to_string(),
+        track
+            .track
+            .musicbrainz_recording_id
+            .as_deref()
+            .map(field)
+            .unwrap_or_default(),
+    ]
+    .join("\t");
+
+    writeln!(file, "{}", entry)
+}
@@ -0,0 +1,870 @@
+//! Submits listens to a Last.fm-protocol (Audioscrobbler 2.0) and/or a
+//! ListenBrainz-compatible service. Finished listens go through a queue in
+//! the library database so they survive being offline or restarting.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use specta::Type;
+use tauri::{AppHandle, Manager};
+use tokio::sync::Notify;
+
+use crate::{
+    error::{CommandError, CommandResult},
+    library_service::library_service,
+    models::{FullTrack, PendingScrobble, ScrobbleService},
+    play_tracker::now_ms,
+    settings::{ManagedSettings, Settings, REDACTED},
+};
+
+/// Submissions per round, ListenBrainz accepts at most 1000 and Last.fm 50.
+const BATCH_SIZE: i64 = 50;
+const MIN_BACKOFF: i64 = 30;
+const MAX_BACKOFF: i64 = 60 * 60;
+/// The worker looks at the queue at least this often, even without new listens.
+const IDLE_CHECK: u64 = 5 * 60;
+
+#[derive(Clone, Serialize, Deserialize, Debug, Type)]
+#[serde(default)]
+pub struct ScrobblerSettings {
+    pub lastfm_enabled: bool,
+    /// Any Audioscrobbler 2.0 endpoint, Libre.fm for example
+    pub lastfm_url: String,
+    pub lastfm_api_key: String,
+    pub lastfm_api_secret: String,
+    pub lastfm_session_key: String,
+    pub listenbrainz_enabled: bool,
+    pub listenbrainz_url: String,
+    pub listenbrainz_token: String,
+}
+
+impl Default for ScrobblerSettings {
+    fn default() -> Self {
+        ScrobblerSettings {
+            lastfm_enabled: false,
+            lastfm_url: "https://ws.audioscrobbler.com/2.0/".to_string(),
+            lastfm_api_key: String::new(),
+            lastfm_api_secret: String::new(),
+            lastfm_session_key: String::new(),
+            listenbrainz_enabled: false,
+            listenbrainz_url: "https://api.listenbrainz.org".to_string(),
+            listenbrainz_token: String::new(),
+        }
+    }
+}
+
+impl ScrobblerSettings {
+    fn services(&self) -> Vec<ScrobbleService> {
+        let mut services = Vec::new();
+        if self.lastfm_enabled && !self.lastfm_session_key.is_empty() {
+            services.push(ScrobbleService::LastFm);
+        }
+        if self.listenbrainz_enabled && !self.listenbrainz_token.is_empty() {
+            services.push(ScrobbleService::ListenBrainz);
+        }
+        services
+    }
+}
+
+#[derive(Clone, Debug)]
+enum SubmitError {
+    /// Worth trying again later: offline, rate limited, service down
+    Retry(String),
+    /// The service refused it and will keep doing so
+    Rejected(String),
+}
+
+/// How each scrobble in a batch the service took fared, in batch order.
+type Submitted = Vec<Result<(), SubmitError>>;
+
+// <------------Commands------------>
+#[tauri::command]
+#[specta::specta]
+pub async fn set_lastfm(
+    state: tauri::State<'_, ManagedSettings>,
+    enabled: bool,
+    url: Option<String>,
+    api_key: Option<String>,
+    api_secret: Option<String>,
+) -> CommandResult<Settings> {
+    state
+        .update(|s| {
+            s.scrobbler.lastfm_enabled = enabled;
+            if let Some(url) = url {
+                s.scrobbler.lastfm_url = url;
+            }
+            if let Some(api_key) = api_key {
+                s.scrobbler.lastfm_api_key = api_key;
+            }
+            if let Some(api_secret) = api_secret.filter(|s| s != REDACTED) {
+                s.scrobbler.lastfm_api_secret = api_secret;
+            }
+        })
+        .await;
+
+    Ok(state.public().await)
+}
+
+/// Trades the user's credentials for a session key, only the key is kept.
+#[tauri::command]
+#[specta::specta]
+pub async fn lastfm_login(
+    state: tauri::State<'_, ManagedSettings>,
+    scrobbler: tauri::State<'_, ManagedScrobbler>,
+    username: String,
+    password: String,
+) -> CommandResult<Settings> {
+    let settings = state.get().await.scrobbler;
+
+    let params = BTreeMap::from([
+        ("method".to_string(), "auth.getMobileSession".to_string()),
+        ("username".to_string(), username),
+        ("password".to_string(), password),
+    ]);
+
+    let response = lastfm_call(&scrobbler.client, &settings, params)
+        .await
+        .map_err(|e| match e {
+            SubmitError::Retry(e) | SubmitError::Rejected(e) => CommandError::Network(e),
+        })?;
+
+    let Some(session_key) = response["session"]["key"].as_str() else {
+        return Err(CommandError::Network(
+            "Last.fm did not return a session".into(),
+        ));
+    };
+
+    state
+        .update(|s| {
+            s.scrobbler.lastfm_session_key = session_key.to_string();
+            s.scrobbler.lastfm_enabled = true;
+        })
+        .await;
+
+    Ok(state.public().await)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn lastfm_logout(state: tauri::State<'_, ManagedSettings>) -> CommandResult<Settings> {
+    state
+        .update(|s| {
+            s.scrobbler.lastfm_session_key.clear();
+            s.scrobbler.lastfm_enabled = false;
+        })
+        .await;
+
+    Ok(state.public().await)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn set_listenbrainz(
+    state: tauri::State<'_, ManagedSettings>,
+    enabled: bool,
+    url: Option<String>,
+    token: Option<String>,
+) -> CommandResult<Settings> {
+    state
+        .update(|s| {
+            s.scrobbler.listenbrainz_enabled = enabled;
+            if let Some(url) = url {
+                s.scrobbler.listenbrainz_url = url;
+            }
+            if let Some(token) = token.filter(|t| t != REDACTED) {
+                s.scrobbler.listenbrainz_token = token;
+            }
+        })
+        .await;
+
+    Ok(state.public().await)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_pending_scrobbles() -> CommandResult<Vec<PendingScrobble>> {
+    Ok(library_service().lock()?.get_all_scrobbles()?)
+}
+
+// <------------Hooks------------>
+/// Announces the track that just started. Best effort, never queued.
+pub fn now_playing(app_handle: &AppHandle, track: &FullTrack) {
+    let app_handle = app_handle.clone();
+    let track = track.clone();
+
+    tauri::async_runtime::spawn(async move {
+        let settings = app_handle.state::<ManagedSettings>().get().await.scrobbler;
+        let scrobbler = app_handle.state::<ManagedScrobbler>();
+        let client = &scrobbler.client;
+
+        for service in settings.services() {
+            let scrobble = PendingScrobble::new(service, &track, now_ms() / 1000);
+            let result = match service {
+                ScrobbleService::LastFm => {
+                    lastfm_submit(client, &settings, &[scrobble], true).await
+                }
+                ScrobbleService::ListenBrainz => {
+                    listenbrainz_submit(client, &settings, &[scrobble], true).await
+                }
+            };
+
+            if let Err(SubmitError::Retry(e) | SubmitError::Rejected(e)) = result {
+                eprintln!("Failed to send now playing: {}", e);
+            }
+        }
+    });
+}
+
+/// Queues a finished listen for every enabled service. `started_at` is a Unix ms timestamp.
+pub async fn scrobble(app_handle: &AppHandle, track: &FullTrack, started_at: i64) {
+    let settings = app_handle.state::<ManagedSettings>().get().await.scrobbler;
+
+    if let Ok(library) = library_service().lock() {
+        for service in settings.services() {
+            let scrobble = PendingScrobble::new(service, track, started_at / 1000);
+            if let Err(e) = library.enqueue_scrobble(&scrobble) {
+                eprintln!("Failed to queue scrobble: {}", e);
+            }
+        }
+    }
+
+    app_handle.state::<ManagedScrobbler>().wake.notify_one();
+}
+
+// <------------Worker------------>
+pub struct ManagedScrobbler {
+    pub client: reqwest::Client,
+    pub wake: Notify,
+}
+
+impl ManagedScrobbler {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .user_agent(concat!("Aurex/", env!("CARGO_PKG_VERSION")))
+                .build()
+                .unwrap_or_default(),
+            wake: Notify::new(),
+        }
+    }
+}
+
+impl Default for ManagedScrobbler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Works through the queue, backing off per scrobble when a service can't be reached.
+pub fn run_scrobbler(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let scrobbler = app_handle.state::<ManagedScrobbler>();
+
+        loop {
+            let settings = app_handle.state::<ManagedSettings>().get().await.scrobbler;
+            submit_due(&scrobbler.client, &settings).await;
+
+            // Disabled services keep their queue until they're turned back on,
+            // but it shouldn't keep waking the loop meanwhile
+            let wait = library_service()
+                .lock()
+                .ok()
+                .and_then(|library| {
+                    library
+                        .next_scrobble_attempt(&settings.services())
+                        .ok()
+                        .flatten()
+                })
+                .map(|next| ((next - now_ms()) / 1000).clamp(1, IDLE_CHECK as i64) as u64)
+                .unwrap_or(IDLE_CHECK);
+
+            tokio::select! {
+                _ = scrobbler.wake.notified() => {}
+                _ = tokio::time::sleep(Duration::from_secs(wait)) => {}
+            }
+        }
+    });
+}
+
+async fn submit_due(client: &reqwest::Client, settings: &ScrobblerSettings) {
+    // Each service gets its own batch, so one with a long backlog can't hold
+    // up the other
+    for service in settings.services() {
+        let batch = match library_service().lock() {
+            Ok(library) => library
+                .get_due_scrobbles(service, now_ms(), BATCH_SIZE)
+                .unwrap_or_default(),
+            Err(_) => return,
+        };
+
+        if batch.is_empty() {
+            continue;
+        }
+
+        let results = submit_batch(client, settings, service, &batch).await;
+
+        let Ok(library) = library_service().lock() else {
+            return;
+        };
+
+        for (scrobble, result) in batch.iter().zip(results) {
+            match result {
+                Ok(()) => {
+                    _ = library.delete_scrobble(scrobble.id);
+                }
+                Err(SubmitError::Rejected(e)) => {
+                    eprintln!("Scrobble rejected, dropping it: {}", e);
+                    _ = library.delete_scrobble(scrobble.id);
+                }
+                Err(SubmitError::Retry(e)) => {
+                    eprintln!("Scrobble failed, retrying later: {}", e);
+                    let backoff = (MIN_BACKOFF << scrobble.attempts.min(10)).min(MAX_BACKOFF);
+                    _ = library.postpone_scrobble(scrobble.id, now_ms() + backoff * 1000);
+                }
+            }
+        }
+    }
+}
+
+/// Submits `batch` and reports on each scrobble in it. A batch refused as a
+/// whole is sent again one scrobble at a time, so only the bad ones get
+/// dropped.
+async fn submit_batch(
+    client: &reqwest::Client,
+    settings: &ScrobblerSettings,
+    service: ScrobbleService,
+    batch: &[PendingScrobble],
+) -> Submitted {
+    match submit(client, settings, service, batch).await {
+        Ok(results) => results,
+        Err(SubmitError::Rejected(_)) if batch.len() > 1 => {
+            let mut results = Vec::with_capacity(batch.len());
+            for scrobble in batch {
+                let single = std::slice::from_ref(scrobble);
+                let result = submit(client, settings, service, single).await;
+                results.push(result.and_then(|mut r| r.pop().unwrap_or(Ok(()))));
+            }
+            results
+        }
+        Err(e) => vec![Err(e); batch.len()],
+    }
+}
+
+async fn submit(
+    client: &reqwest::Client,
+    settings: &ScrobblerSettings,
+    service: ScrobbleService,
+    scrobbles: &[PendingScrobble],
+) -> Result<Submitted, SubmitError> {
+    match service {
+        ScrobbleService::LastFm => lastfm_submit(client, settings, scrobbles, false).await,
+        ScrobbleService::ListenBrainz => {
+            listenbrainz_submit(client, settings, scrobbles, false).await
+        }
+    }
+}
+
+// <------------Last.fm------------>
+// Error codes worth retrying: 8 operation failed, 9 invalid session (until the
+// user logs in again), 11 service offline, 16 temporarily unavailable, 29 rate limit
+const LASTFM_RETRY_CODES: [i64; 5] = [8, 9, 11, 16, 29];
+// Scrobbles Last.fm takes but ignores are only worth retrying when the daily
+// limit (5) was hit, the rest are filtered artists or tracks and bad timestamps
+const LASTFM_IGNORED_RETRY_CODE: i64 = 5;
+
+async fn lastfm_submit(
+    client: &reqwest::Client,
+    settings: &ScrobblerSettings,
+    scrobbles: &[PendingScrobble],
+    now_playing: bool,
+) -> Result<Submitted, SubmitError> {
+    let method = match now_playing {
+        true => "track.updateNowPlaying",
+        false => "track.scrobble",
+    };
+
+    let mut params = BTreeMap::from([
+        ("method".to_string(), method.to_string()),
+        ("sk".to_string(), settings.lastfm_session_key.clone()),
+    ]);
+
+    // Batches use indexed keys, a single now playing doesn't
+    for (i, scrobble) in scrobbles.iter().enumerate() {
+        let key = |name: &str| match now_playing {
+            true => name.to_string(),
+            false => format!("{name}[{i}]"),
+        };
+
+        let mut fields = vec![
+            (key("artist"), scrobble.artist.clone()),
+            (key("track"), scrobble.title.clone()),
+            (key("album"), scrobble.album.clone()),
+            (key("duration"), scrobble.duration.to_string()),
+        ];
+        if !now_playing {
+            fields.push((key("timestamp"), scrobble.timestamp.to_string()));
+        }
+        if scrobble.track_number > 0 {
+            fields.push((key("trackNumber"), scrobble.track_number.to_string()));
+        }
+        if let Some(mbid) = &scrobble.mbid {
+            fields.push((key("mbid"), mbid.clone()));
+        }
+        params.extend(fields);
+    }
+
+    let body = lastfm_call(client, settings, params).await?;
+
+    Ok(match now_playing {
+        true => vec![Ok(())],
+        false => lastfm_ignored(&body, scrobbles.len()),
+    })
+}
+
+/// Reads which scrobbles of an accepted batch Last.fm ignored. A single
+/// scrobble comes back as an object rather than a list of one, and services
+/// that don't report on each scrobble have them all taken.
+fn lastfm_ignored(body: &Value, count: usize) -> Submitted {
+    let scrobbles = match &body["scrobbles"]["scrobble"] {
+        Value::Array(scrobbles) => scrobbles.clone(),
+        Value::Object(_) => vec![body["scrobbles"]["scrobble"].clone()],
+        _ => Vec::new(),
+    };
+
+    (0..count)
+        .map(|i| {
+            let ignored = scrobbles.get(i).map(|s| &s["ignoredMessage"]);
+            let code = ignored
+                .and_then(|m| match &m["code"] {
+                    Value::String(code) => code.parse().ok(),
+                    code => code.as_i64(),
+                })
+                .unwrap_or(0);
+
+            let message = || {
+                let text = ignored
+                    .and_then(|m| m["#text"].as_str())
+                    .unwrap_or_default();
+                format!("Ignored by Last.fm ({code}) {text}")
+                    .trim_end()
+                    .to_string()
+            };
+
+            match code {
+                0 => Ok(()),
+                LASTFM_IGNORED_RETRY_CODE => Err(SubmitError::Retry(message())),
+                _ => Err(SubmitError::Rejected(message())),
+            }
+        })
+        .collect()
+}
+
+/// Signs and sends an API call, returning the decoded response.
+async fn lastfm_call(
+    client: &reqwest::Client,
+    settings: &ScrobblerSettings,
+    mut params: BTreeMap<String, String>,
+) -> Result<Value, SubmitError> {
+    params.insert("api_key".to_string(), settings.lastfm_api_key.clone());
+
+    // The signature covers every parameter sorted by name, then the secret
+    let mut signature = String::new();
+    for (key, value) in &params {
+        signature.push_str(key);
+        signature.push_str(value);
+    }
+    signature.push_str(&settings.lastfm_api_secret);
+    params.insert(
+        "api_sig".to_string(),
+        format!("{:x}", md5::compute(signature)),
+    );
+    params.insert("format".to_string(), "json".to_string());
+
+    let response = client
+        .post(&settings.lastfm_url)
+        .form(&params)
+        .send()
+        .await
+        .map_err(|e| SubmitError::Retry(e.to_string()))?;
+
+    let status = response.status();
+    let body: Value = response
+        .json()
+        .await
+        .map_err(|e| SubmitError::Retry(format!("{}: {}", status, e)))?;
+
+    match body["error"].as_i64() {
+        None if status.is_success() => Ok(body),
+        None => Err(SubmitError::Retry(status.to_string())),
+        Some(code) => {
+            let message = body["message"].as_str().unwrap_or_default().to_string();
+            if LASTFM_RETRY_CODES.contains(&code) || status.is_server_error() {
+                Err(SubmitError::Retry(message))
+            } else {
+                Err(SubmitError::Rejected(message))
+            }
+        }
+    }
+}
+
+// <------------ListenBrainz------------>
+async fn listenbrainz_submit(
+    client: &reqwest::Client,
+    settings: &ScrobblerSettings,
+    scrobbles: &[PendingScrobble],
+    now_playing: bool,
+) -> Result<Submitted, SubmitError> {
+    let payload: Vec<Value> = scrobbles
+        .iter()
+        .map(|scrobble| {
+            let mut additional_info = json!({
+                "duration_ms": scrobble.duration * 1000,
+                "submission_client": "Aurex",
+                "submission_client_version": env!("CARGO_PKG_VERSION"),
+            });
+            if scrobble.track_number > 0 {
+                additional_info["tracknumber"] = json!(scrobble.track_number);
+            }
+            if let Some(mbid) = &scrobble.mbid {
+                additional_info["recording_mbid"] = json!(mbid);
+            }
+
+            let mut listen = json!({
+                "track_metadata": {
+                    "artist_name": scrobble.artist,
+                    "track_name": scrobble.title,
+                    "release_name": scrobble.album,
+                    "additional_info": additional_info,
+                }
+            });
+            if !now_playing {
+                listen["listened_at"] = json!(scrobble.timestamp);
+            }
+            listen
+        })
+        .collect();
+
+    let listen_type = match (now_playing, payload.len()) {
+        (true, _) => "playing_now",
+        (false, 1) => "single",
+        (false, _) => "import",
+    };
+
+    let url = format!(
+        "{}/1/submit-listens",
+        settings.listenbrainz_url.trim_end_matches('/')
+    );
+    let response = client
+        .post(url)
+        .header(
+            "Authorization",
+            format!("Token {}", settings.listenbrainz_token),
+        )
+        .json(&json!({ "listen_type": listen_type, "payload": payload }))
+        .send()
+        .await
+        .map_err(|e| SubmitError::Retry(e.to_string()))?;
+
+    let status = response.status();
+    // Listens are taken or refused together
+    if status.is_success() {
+        return Ok(vec![Ok(()); scrobbles.len()]);
+    }
+
+    let message = response.text().await.unwrap_or_else(|_| status.to_string());
+
+    // A bad token gets fixed by the user, not by dropping their listens
+    if status.is_server_error() || status.as_u16() == 401 || status.as_u16() == 429 {
+        Err(SubmitError::Retry(message))
+    } else {
+        Err(SubmitError::Rejected(message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::{
+        extract::{Form, Json, State},
+        http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+        routing::post,
+        Router,
+    };
+
+    use super::*;
+
+    /// What the stand-in service was sent last.
+    #[derive(Default)]
+    struct Received {
+        form: BTreeMap<String, String>,
+        json: Value,
+        authorization: Option<String>,
+    }
+
+    type Shared = Arc<Mutex<Received>>;
+
+    /// Serves Last.fm at `/2.0/` and ListenBrainz at `/1/submit-listens` on a
+    /// local port, both answering with `status` and `body`. Returns the base URL.
+    async fn stand_in(status: u16, body: &'static str) -> (String, Shared) {
+        let status = StatusCode::from_u16(status).unwrap();
+        let received = Shared::default();
+
+        let router = Router::new()
+            .route(
+                "/2.0/",
+                post(
+                    move |State(received): State<Shared>,
+                          Form(form): Form<BTreeMap<String, String>>| async move {
+                        received.lock().unwrap().form = form;
+                        (status, body)
+                    },
+                ),
+            )
+            .route(
+                "/1/submit-listens",
+                post(
+                    move |State(received): State<Shared>,
+                          headers: HeaderMap,
+                          Json(json): Json<Value>| async move {
+                        let mut received = received.lock().unwrap();
+                        received.json = json;
+                        received.authorization = headers
+                            .get(AUTHORIZATION)
+                            .and_then(|v| v.to_str().ok())
+                            .map(str::to_string);
+                        (status, body)
+                    },
+                ),
+            )
+            .with_state(received.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tauri::async_runtime::spawn(async move { axum::serve(listener, router).await });
+
+        (url, received)
+    }
+
+    fn settings(url: &str) -> ScrobblerSettings {
+        ScrobblerSettings {
+            lastfm_enabled: true,
+            lastfm_url: format!("{url}/2.0/"),
+            lastfm_api_key: "key".to_string(),
+            lastfm_api_secret: "secret".to_string(),
+            lastfm_session_key: "session".to_string(),
+            listenbrainz_enabled: true,
+            listenbrainz_url: format!("{url}/"),
+            listenbrainz_token: "token".to_string(),
+        }
+    }
+
+    fn scrobbles(service: ScrobbleService, count: i64) -> Vec<PendingScrobble> {
+        (1..=count)
+            .map(|id| {
+                let mut track = FullTrack::stub(id, 1);
+                track.artist_name = format!("Artist {id}");
+                track.album_title = "Album".to_string();
+                track.track.track_number = id;
+                track.track.duration = 200;
+                PendingScrobble::new(service, &track, 1_700_000_000 + id * 200)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lastfm_scrobbles_signed_batch() {
+        tauri::async_runtime::block_on(async {
+            let (url, received) = stand_in(200, r#"{"scrobbles":{}}"#).await;
+            let client = reqwest::Client::new();
+            let batch = scrobbles(ScrobbleService::LastFm, 2);
+
+            let result = lastfm_submit(&client, &settings(&url), &batch, false).await;
+            assert!(result.is_ok());
+
+            let mut form = std::mem::take(&mut received.lock().unwrap().form);
+            assert_eq!(form["method"], "track.scrobble");
+            assert_eq!(form["sk"], "session");
+            assert_eq!(form["api_key"], "key");
+            assert_eq!(form["artist[0]"], "Artist 1");
+            assert_eq!(form["track[1]"], "Track 2");
+            assert_eq!(form["trackNumber[1]"], "2");
+            assert_eq!(form["timestamp[0]"], "1700000200");
+            assert_eq!(form.remove("format").as_deref(), Some("json"));
+
+            let signature = form.remove("api_sig").unwrap();
+            let signed: String = form.iter().map(|(k, v)| format!("{k}{v}")).collect();
+            assert_eq!(signature, format!("{:x}", md5::compute(signed + "secret")));
+        });
+    }
+
+    #[test]
+    fn lastfm_now_playing_is_not_indexed() {
+        tauri::async_runtime::block_on(async {
+            let (url, received) = stand_in(200, r#"{"nowplaying":{}}"#).await;
+            let client = reqwest::Client::new();
+            let batch = scrobbles(ScrobbleService::LastFm, 1);
+
+            let result = lastfm_submit(&client, &settings(&url), &batch, true).await;
+            assert!(result.is_ok());
+
+            let form = &received.lock().unwrap().form;
+            assert_eq!(form["method"], "track.updateNowPlaying");
+            assert_eq!(form["artist"], "Artist 1");
+            assert!(!form.contains_key("timestamp"));
+        });
+    }
+
+    #[test]
+    fn lastfm_errors_are_retried_or_dropped() {
+        tauri::async_runtime::block_on(async {
+            let client = reqwest::Client::new();
+            let batch = scrobbles(ScrobbleService::LastFm, 1);
+
+            let submit = |status, body| {
+                let client = client.clone();
+                let batch = batch.clone();
+                async move {
+                    let (url, _) = stand_in(status, body).await;
+                    lastfm_submit(&client, &settings(&url), &batch, false).await
+                }
+            };
+
+            let rate_limited = submit(200, r#"{"error":29,"message":"Rate limit"}"#).await;
+            assert!(matches!(rate_limited, Err(SubmitError::Retry(m)) if m == "Rate limit"));
+
+            let invalid = submit(400, r#"{"error":6,"message":"Invalid parameters"}"#).await;
+            assert!(matches!(invalid, Err(SubmitError::Rejected(_))));
+
+            let down = submit(503, "Service Unavailable").await;
+            assert!(matches!(down, Err(SubmitError::Retry(_))));
+        });
+    }
+
+    #[test]
+    fn lastfm_ignored_scrobbles_are_dropped_or_retried() {
+        tauri::async_runtime::block_on(async {
+            let body = r##"{"scrobbles":{"scrobble":[
+                {"ignoredMessage":{"code":"0","#text":""}},
+                {"ignoredMessage":{"code":"1","#text":"Artist was ignored"}},
+                {"ignoredMessage":{"code":"5","#text":"Daily scrobble limit exceeded"}}
+            ],"@attr":{"accepted":1,"ignored":2}}}"##;
+            let (url, _) = stand_in(200, body).await;
+            let client = reqwest::Client::new();
+            let batch = scrobbles(ScrobbleService::LastFm, 3);
+
+            let results = lastfm_submit(&client, &settings(&url), &batch, false)
+                .await
+                .unwrap();
+            assert!(matches!(results[0], Ok(())));
+            assert!(
+                matches!(&results[1], Err(SubmitError::Rejected(m)) if m.contains("Artist was ignored"))
+            );
+            assert!(matches!(results[2], Err(SubmitError::Retry(_))));
+
+            // A single scrobble isn't wrapped in a list
+            let body = r##"{"scrobbles":{"scrobble":{"ignoredMessage":{"code":"3","#text":""}}}}"##;
+            let (url, _) = stand_in(200, body).await;
+            let single = &batch[..1];
+            let results = lastfm_submit(&client, &settings(&url), single, false)
+                .await
+                .unwrap();
+            assert!(matches!(results[..], [Err(SubmitError::Rejected(_))]));
+        });
+    }
+
+    #[test]
+    fn rejected_batches_are_retried_one_by_one() {
+        tauri::async_runtime::block_on(async {
+            // Refuses every batch holding Artist 2, like a service validating
+            // the whole submission at once
+            let router = Router::new().route(
+                "/1/submit-listens",
+                post(|Json(json): Json<Value>| async move {
+                    match json.to_string().contains("Artist 2") {
+                        true => (StatusCode::BAD_REQUEST, r#"{"error":"bad listen"}"#),
+                        false => (StatusCode::OK, r#"{"status":"ok"}"#),
+                    }
+                }),
+            );
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            tauri::async_runtime::spawn(async move { axum::serve(listener, router).await });
+
+            let client = reqwest::Client::new();
+            let service = ScrobbleService::ListenBrainz;
+            let batch = scrobbles(service, 3);
+
+            let results = submit_batch(&client, &settings(&url), service, &batch).await;
+            assert_eq!(results.len(), 3);
+            assert!(matches!(results[0], Ok(())));
+            assert!(matches!(results[1], Err(SubmitError::Rejected(_))));
+            assert!(matches!(results[2], Ok(())));
+        });
+    }
+
+    #[test]
+    fn listenbrainz_submits_listens() {
+        tauri::async_runtime::block_on(async {
+            let (url, received) = stand_in(200, r#"{"status":"ok"}"#).await;
+            let client = reqwest::Client::new();
+            let settings = settings(&url);
+
+            let single = scrobbles(ScrobbleService::ListenBrainz, 1);
+            assert!(listenbrainz_submit(&client, &settings, &single, false)
+                .await
+                .is_ok());
+            {
+                let received = received.lock().unwrap();
+                assert_eq!(received.authorization.as_deref(), Some("Token token"));
+                assert_eq!(received.json["listen_type"], "single");
+
+                let listen = &received.json["payload"][0];
+                assert_eq!(listen["listened_at"], 1_700_000_200);
+                assert_eq!(listen["track_metadata"]["artist_name"], "Artist 1");
+                assert_eq!(listen["track_metadata"]["track_name"], "Track 1");
+                let info = &listen["track_metadata"]["additional_info"];
+                assert_eq!(info["duration_ms"], 200_000);
+                assert_eq!(info["tracknumber"], 1);
+            }
+
+            let batch = scrobbles(ScrobbleService::ListenBrainz, 3);
+            assert!(listenbrainz_submit(&client, &settings, &batch, false)
+                .await
+                .is_ok());
+            {
+                let received = received.lock().unwrap();
+                assert_eq!(received.json["listen_type"], "import");
+                assert_eq!(received.json["payload"].as_array().unwrap().len(), 3);
+            }
+
+            assert!(listenbrainz_submit(&client, &settings, &single, true)
+                .await
+                .is_ok());
+            let received = received.lock().unwrap();
+            assert_eq!(received.json["listen_type"], "playing_now");
+            assert!(received.json["payload"][0].get("listened_at").is_none());
+        });
+    }
+
+    #[test]
+    fn listenbrainz_errors_are_retried_or_dropped() {
+        tauri::async_runtime::block_on(async {
+            let client = reqwest::Client::new();
+            let batch = scrobbles(ScrobbleService::ListenBrainz, 1);
+
+            for (status, retried) in [(401, true), (429, true), (500, true), (400, false)] {
+                let (url, _) = stand_in(status, r#"{"error":"nope"}"#).await;
+                let result = listenbrainz_submit(&client, &settings(&url), &batch, false).await;
+
+                match result {
+                    Err(SubmitError::Retry(_)) => assert!(retried, "{status} was retried"),
+                    Err(SubmitError::Rejected(_)) => assert!(!retried, "{status} was dropped"),
+                    Ok(_) => panic!("{status} was accepted"),
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,195 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, PoisonError,
+    },
+    time::Duration,
+};
+
+use crate::{
+    audio_player::{LoopType, PlaybackContext, QueueEntry, ShuffleStrategy},
+    constants::session_file,
+    library_service::library_service,
+    models::FullTrack,
+};
+
+/// Player updates come in bursts, the session is written once they settle.
+const SAVE_DELAY: Duration = Duration::from_secs(2);
+
+/// The newest session not written yet, and whether a write is on its way.
+static PENDING: Mutex<Option<Session>> = Mutex::new(None);
+static SCHEDULED: AtomicBool = AtomicBool::new(false);
+
+/// On-disk snapshot of the player. Tracks are stored by id and resolved
+/// against the library again on startup.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct Session {
+    pub currently_playing: Option<i64>,
+    pub history: Vec<i64>,
+    pub real_queue: Vec<SavedEntry>,
+    pub queue: Vec<SavedEntry>,
+    pub shuffle: bool,
+    pub position: f64,
+    pub looping: LoopType,
+    pub shuffle_strategy: ShuffleStrategy,
+    pub autoplay: bool,
+    pub context: Option<PlaybackContext>,
+    pub context_tracks: Vec<i64>,
+}
+
+/// A queue entry as it was when saved. The shuffled queue refers back to
+/// entries of the real queue through `entry_id`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SavedEntry {
+    pub entry_id: u32,
+    pub track_id: i64,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session {
+            currently_playing: None,
+            history: Vec::new(),
+            real_queue: Vec::new(),
+            queue: Vec::new(),
+            shuffle: false,
+            position: 0.0,
+            looping: LoopType::Off,
+            shuffle_strategy: ShuffleStrategy::Uniform,
+            autoplay: false,
+            context: None,
+            context_tracks: Vec::new(),
+        }
+    }
+}
+
+pub fn load() -> Option<Session> {
+    let content = fs::read_to_string(session_file()).ok()?;
+
+    match serde_json::from_str(&content) {
+        Ok(session) => Some(session),
+        Err(e) => {
+            eprintln!("Failed to parse session: {}", e);
+            None
+        }
+    }
+}
+
+/// Queues the session to be written shortly, replacing one still waiting.
+pub fn save(session: Session) {
+    *PENDING.lock().unwrap_or_else(PoisonError::into_inner) = Some(session);
+
+    if SCHEDULED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+
+    tauri::async_runtime::spawn(async {
+        tokio::time::sleep(SAVE_DELAY).await;
+
+        // Cleared first so a save coming in during the write gets its own
+        SCHEDULED.store(false, Ordering::Release);
+
+        _ = tauri::async_runtime::spawn_blocking(|| {
+            // Locked through the write so an older session can't land after `save_now`
+            let mut pending = PENDING.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some(session) = pending.take() {
+                write(&session);
+            }
+        })
+        .await;
+    });
+}
+
+/// Writes the session right away, dropping any that was still waiting.
+pub fn save_now(session: &Session) {
+    let mut pending = PENDING.lock().unwrap_or_else(PoisonError::into_inner);
+    pending.take();
+
+    write(session);
+}
+
+/// Goes through a temporary file so a crash mid-write can't leave half a session.
+fn write(session: &Session) {
+    let json = match serde_json::to_string(session) {
+        Ok(json) => json,
+        Err(e) => {
+            eprintln!("Failed to serialize session: {}", e);
+            return;
+        }
+    };
+
+    let path = session_file();
+    let temp = path.with_extension("json.tmp");
+
+    if let Err(e) = fs::write(&temp, json).and_then(|_| fs::rename(&temp, &path)) {
+        eprintln!("Failed to write session: {}", e);
+    }
+}
+
+pub fn track_ids<'a>(tracks: impl IntoIterator<Item = &'a FullTrack>) -> Vec<i64> {
+    tracks.into_iter().filter_map(|t| t.track.id).collect()
+}
+
+pub fn entry_ids<'a>(entries: impl IntoIterator<Item = &'a QueueEntry>) -> Vec<SavedEntry> {
+    entries
+        .into_iter()
+        .filter_map(|e| {
+            e.track.track.id.map(|track_id| SavedEntry {
+                entry_id: e.entry_id,
+                track_id,
+            })
+        })
+        .collect()
+}
+
+/// Rebuilds both queues with fresh entries, keeping the shuffled one pointing
+/// at the same entries as the real one.
+pub fn resolve_queue(
+    real_queue: &[SavedEntry],
+    queue: &[SavedEntry],
+) -> (VecDeque<QueueEntry>, VecDeque<QueueEntry>) {
+    let track_ids: Vec<i64> = real_queue.iter().map(|e| e.track_id).collect();
+    let tracks = resolve(&track_ids);
+
+    let mut entries: HashMap<u32, QueueEntry> = HashMap::new();
+    let mut resolved_real = VecDeque::new();
+
+    for saved in real_queue {
+        let Some(track) = tracks.iter().find(|t| t.track.id == Some(saved.track_id)) else {
+            continue;
+        };
+
+        let entry = QueueEntry::new(track.clone());
+        entries.insert(saved.entry_id, entry.clone());
+        resolved_real.push_back(entry);
+    }
+
+    let resolved_queue = queue
+        .iter()
+        .filter_map(|saved| entries.get(&saved.entry_id).cloned())
+        .collect();
+
+    (resolved_real, resolved_queue)
+}
+
+/// Looks the ids up in the library, quietly dropping anything that was
+/// removed from the library or from disk since the session was saved.
+pub fn resolve(ids: &[i64]) -> VecDeque<FullTrack> {
+    if ids.is_empty() {
+        return VecDeque::new();
+    }
+
+    let Ok(library) = library_service().lock() else {
+        return VecDeque::new();
+    };
+
+    ids.iter()
+        .filter_map(|id| library.get_full_track_by_id(*id).ok().flatten())
+        .filter(|t| Path::new(t.track.audio_path()).exists())
+        .collect()
+}
@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+use std::{fs, sync::Arc};
+use tauri::{AppHandle, Emitter};
+use tokio::sync::Mutex;
+
+use specta::Type;
+
+use crate::{
+    constants::settings_file, error::CommandResult, remote::RemoteSettings,
+    scrobbler::ScrobblerSettings,
+};
+
+pub const MIN_PROGRESS_INTERVAL: u32 = 16;
+pub const MAX_PROGRESS_INTERVAL: u32 = 1000;
+
+/// Shown in place of a secret that is set. Setters ignore it coming back.
+pub const REDACTED: &str = "********";
+
+// <------------State------------>
+#[derive(Clone, Serialize, Deserialize, Debug, Type)]
+#[serde(default)]
+pub struct Settings {
+    /// Tracks at least this many seconds long resume where they were left, 0 turns it off
+    pub long_form_min_length: f64,
+    /// Milliseconds between `progress-changed` events while playing
+    pub progress_interval: u32,
+    pub remote: RemoteSettings,
+    pub scrobbler: ScrobblerSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            long_form_min_length: 20.0 * 60.0,
+            progress_interval: 100,
+            remote: RemoteSettings::default(),
+            scrobbler: ScrobblerSettings::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// A copy for the frontend, with the remote token and the scrobbler
+    /// secrets masked. Unset ones stay empty so the UI can tell.
+    fn redacted(&self) -> Settings {
+        let mask = |secret: &mut String| {
+            if !secret.is_empty() {
+                *secret = REDACTED.to_string();
+            }
+        };
+
+        let mut settings = self.clone();
+        mask(&mut settings.remote.token);
+        mask(&mut settings.scrobbler.lastfm_api_secret);
+        mask(&mut settings.scrobbler.lastfm_session_key);
+        mask(&mut settings.scrobbler.listenbrainz_token);
+        settings
+    }
+}
+
+// <------------Commands------------>
+#[tauri::command]
+#[specta::specta]
+pub async fn get_settings(state: tauri::State<'_, ManagedSettings>) -> CommandResult<Settings> {
+    Ok(state.public().await)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn set_long_form_min_length(
+    state: tauri::State<'_, ManagedSettings>,
+    seconds: f64,
+) -> CommandResult<Settings> {
+    state
+        .update(|s| {
+            s.long_form_min_length = seconds.max(0.0);
+        })
+        .await;
+
+    Ok(state.public().await)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn set_progress_interval(
+    state: tauri::State<'_, ManagedSettings>,
+    milliseconds: u32,
+) -> CommandResult<Settings> {
+    state
+        .update(|s| {
+            s.progress_interval = milliseconds.clamp(MIN_PROGRESS_INTERVAL, MAX_PROGRESS_INTERVAL);
+        })
+        .await;
+
+    Ok(state.public().await)
+}
+
+// <------------Manager------------>
+pub struct ManagedSettings {
+    pub settings: Arc<Mutex<Settings>>,
+    pub app: AppHandle,
+}
+
+impl ManagedSettings {
+    pub fn new(app: AppHandle) -> Self {
+        let settings = fs::read_to_string(settings_file())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+
+        Self {
+            settings: Arc::new(Mutex::new(settings)),
+            app,
+        }
+    }
+
+    pub async fn update<F>(&self, updater: F)
+    where
+        F: FnOnce(&mut Settings),
+    {
+        let mut settings = self.settings.lock().await;
+        updater(&mut *settings);
+
+        match serde_json::to_string_pretty(&*settings) {
+            Ok(json) => {
+                if let Err(e) = fs::write(settings_file(), json) {
+                    eprintln!("Failed to write settings: {}", e);
+                }
+            }
+            Err(e) => eprintln!("Failed to serialize settings: {}", e),
+        }
+
+        let _ = self.app.emit("settings-changed", settings.redacted());
+    }
+
+    pub async fn get(&self) -> Settings {
+        self.settings.lock().await.clone()
+    }
+
+    /// The settings as the frontend gets to see them.
+    pub async fn public(&self) -> Settings {
+        self.settings.lock().await.redacted()
+    }
+}
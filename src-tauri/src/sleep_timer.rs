@@ -0,0 +1,146 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::{
+    audio_player::{pause, AudioPlayer, ManagedPlayer, PlayerState},
+    error::CommandResult,
+};
+
+const TICK: f64 = 1.0;
+
+#[derive(Clone, Serialize, Deserialize, Debug, Type, PartialEq)]
+pub enum SleepTimer {
+    /// Pause once `remaining` seconds run out
+    Duration { remaining: f64 },
+    /// Stop once this many more tracks have finished, 0 being the current one
+    AfterTracks { tracks: u32 },
+}
+
+pub fn run_sleep_timer(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs_f64(TICK));
+
+        loop {
+            interval.tick().await;
+            tick(&app_handle).await;
+        }
+    });
+}
+
+/// Counts a `Duration` timer down while something is playing. The time left
+/// goes out on its own event, without saving the session or resending the
+/// whole player every second.
+async fn tick(app_handle: &AppHandle) {
+    let state = app_handle.state::<ManagedPlayer>();
+
+    let expired = {
+        let mut player = state.player.lock().await;
+        if player.state != PlayerState::Playing {
+            return;
+        }
+
+        let Some(SleepTimer::Duration { remaining }) = &mut player.sleep_timer else {
+            return;
+        };
+
+        *remaining = (*remaining - TICK).max(0.0);
+        let expired = *remaining <= 0.0;
+
+        _ = app_handle.emit("sleep-timer-changed", player.sleep_timer.clone());
+        expired
+    };
+
+    if expired {
+        fire(&state).await;
+    }
+}
+
+/// Counts a finished track against an `AfterTracks` timer. Returns true when
+/// playback should stop now.
+pub async fn track_finished(state: &ManagedPlayer) -> bool {
+    let mut stop = false;
+
+    let counting = matches!(
+        state.player.lock().await.sleep_timer,
+        Some(SleepTimer::AfterTracks { .. })
+    );
+    if !counting {
+        return false;
+    }
+
+    state
+        .update(|player| {
+            if let Some(SleepTimer::AfterTracks { tracks }) = &mut player.sleep_timer {
+                match tracks.checked_sub(1) {
+                    Some(left) => *tracks = left,
+                    None => stop = true,
+                }
+            }
+        })
+        .await;
+
+    stop
+}
+
+/// Pauses playback and lets the UI know.
+pub async fn fire(state: &tauri::State<'_, ManagedPlayer>) {
+    state
+        .update(|player| {
+            player.sleep_timer = None;
+        })
+        .await;
+
+    _ = pause(state.clone()).await;
+
+    _ = state.app.emit("sleep-timer-fired", ());
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn set_sleep_timer(
+    state: tauri::State<'_, ManagedPlayer>,
+    minutes: f64,
+) -> CommandResult<AudioPlayer> {
+    let remaining = (minutes * 60.0).max(0.0);
+
+    state
+        .update(|player| {
+            player.sleep_timer = Some(SleepTimer::Duration { remaining });
+        })
+        .await;
+
+    Ok(state.get().await)
+}
+
+/// 0 stops at the end of the current track.
+#[tauri::command]
+#[specta::specta]
+pub async fn stop_after_tracks(
+    state: tauri::State<'_, ManagedPlayer>,
+    tracks: u32,
+) -> CommandResult<AudioPlayer> {
+    state
+        .update(|player| {
+            player.sleep_timer = Some(SleepTimer::AfterTracks { tracks });
+        })
+        .await;
+
+    Ok(state.get().await)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn cancel_sleep_timer(
+    state: tauri::State<'_, ManagedPlayer>,
+) -> CommandResult<AudioPlayer> {
+    state
+        .update(|player| {
+            player.sleep_timer = None;
+        })
+        .await;
+
+    Ok(state.get().await)
+}
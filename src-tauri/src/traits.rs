@@ -1,6 +1,7 @@
 use rand::rng;
 use rand::seq::SliceRandom;
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
 
 pub trait Shuffle {
     fn shuffle(&mut self);
@@ -14,3 +15,113 @@ impl<T> Shuffle for VecDeque<T> {
         slice.shuffle(&mut rng);
     }
 }
+
+pub trait BalancedShuffle<T> {
+    /// Shuffles so that items sharing a `primary` key end up spread evenly
+    /// through the whole list, and within those, items sharing a `secondary` key.
+    fn balanced_shuffle<P, S>(&mut self, primary: impl Fn(&T) -> P, secondary: impl Fn(&T) -> S)
+    where
+        P: Hash + Eq,
+        S: Hash + Eq;
+}
+
+impl<T> BalancedShuffle<T> for VecDeque<T> {
+    fn balanced_shuffle<P, S>(&mut self, primary: impl Fn(&T) -> P, secondary: impl Fn(&T) -> S)
+    where
+        P: Hash + Eq,
+        S: Hash + Eq,
+    {
+        let items: Vec<T> = self.drain(..).collect();
+
+        let spread_out = spread(items, primary, |group| {
+            spread(group, &secondary, |mut inner| {
+                inner.shuffle(&mut rng());
+                inner
+            })
+        });
+
+        self.extend(spread_out);
+    }
+}
+
+/// Gives every group of items sharing a key evenly spaced slots across
+/// 0..1, starting at a random offset, then merges all groups by slot.
+fn spread<T, K: Hash + Eq>(
+    items: Vec<T>,
+    key: impl Fn(&T) -> K,
+    order_group: impl Fn(Vec<T>) -> Vec<T>,
+) -> Vec<T> {
+    let mut groups: HashMap<K, Vec<T>> = HashMap::new();
+    for item in items {
+        groups.entry(key(&item)).or_default().push(item);
+    }
+
+    let mut slotted: Vec<(f64, T)> = Vec::new();
+
+    for (_, group) in groups {
+        let group = order_group(group);
+        let n = group.len() as f64;
+        let offset = rand::random::<f64>() / n;
+
+        for (i, item) in group.into_iter().enumerate() {
+            // A little jitter so groups of the same size don't interleave in lockstep
+            let jitter = (rand::random::<f64>() - 0.5) * 0.2 / n;
+            slotted.push((offset + i as f64 / n + jitter, item));
+        }
+    }
+
+    slotted.sort_by(|a, b| a.0.total_cmp(&b.0));
+    slotted.into_iter().map(|(_, item)| item).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (artist, album, id)
+    fn tracks(artists: &[(u32, usize)]) -> VecDeque<(u32, u32, usize)> {
+        let mut tracks = VecDeque::new();
+        for &(artist, count) in artists {
+            for i in 0..count {
+                tracks.push_back((artist, i as u32 % 2, tracks.len()));
+            }
+        }
+        tracks
+    }
+
+    #[test]
+    fn balanced_shuffle_keeps_every_item() {
+        let mut queue = tracks(&[(1, 4), (2, 3), (3, 1)]);
+        queue.balanced_shuffle(|t| t.0, |t| t.1);
+
+        let mut ids: Vec<usize> = queue.iter().map(|t| t.2).collect();
+        ids.sort();
+        assert_eq!(ids, (0..8).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn balanced_shuffle_handles_empty_and_single() {
+        let mut empty: VecDeque<(u32, u32, usize)> = VecDeque::new();
+        empty.balanced_shuffle(|t| t.0, |t| t.1);
+        assert!(empty.is_empty());
+
+        let mut single = tracks(&[(1, 1)]);
+        single.balanced_shuffle(|t| t.0, |t| t.1);
+        assert_eq!(single, tracks(&[(1, 1)]));
+    }
+
+    #[test]
+    fn balanced_shuffle_spreads_artists_apart() {
+        // Two artists with five tracks each never get three in a row
+        for _ in 0..200 {
+            let mut queue = tracks(&[(1, 5), (2, 5)]);
+            queue.balanced_shuffle(|t| t.0, |t| t.1);
+
+            let artists: Vec<u32> = queue.iter().map(|t| t.0).collect();
+            assert!(
+                artists.windows(3).all(|w| !(w[0] == w[1] && w[1] == w[2])),
+                "{artists:?}"
+            );
+        }
+    }
+}
@@ -3,7 +3,7 @@
 /** user-defined commands **/
 
 export const commands = {
-  async getState(): Promise<Result<AppState, string>> {
+  async getState(): Promise<Result<AppState, CommandError>> {
     try {
       return { status: "ok", data: await TAURI_INVOKE("get_state") };
     } catch (e) {
@@ -11,7 +11,7 @@ export const commands = {
       else return { status: "error", error: e as any };
     }
   },
-  async incrementClick(): Promise<Result<null, string>> {
+  async incrementClick(): Promise<Result<null, CommandError>> {
     try {
       return { status: "ok", data: await TAURI_INVOKE("increment_click") };
     } catch (e) {
@@ -19,7 +19,7 @@ export const commands = {
       else return { status: "error", error: e as any };
     }
   },
-  async resetClicks(): Promise<Result<null, string>> {
+  async resetClicks(): Promise<Result<null, CommandError>> {
     try {
       return { status: "ok", data: await TAURI_INVOKE("reset_clicks") };
     } catch (e) {
@@ -27,83 +27,280 @@ export const commands = {
       else return { status: "error", error: e as any };
     }
   },
-  async index(): Promise<void> {
-    await TAURI_INVOKE("index");
+  async index(): Promise<Result<null, CommandError>> {
+    try {
+      return { status: "ok", data: await TAURI_INVOKE("index") };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
   },
-  async getAllTracks(): Promise<FullTrack[]> {
-    return await TAURI_INVOKE("get_all_tracks");
+  async getAllTracks(): Promise<Result<FullTrack[], CommandError>> {
+    try {
+      return { status: "ok", data: await TAURI_INVOKE("get_all_tracks") };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
   },
-  async getAllAlbums(): Promise<Album[]> {
-    return await TAURI_INVOKE("get_all_albums");
+  async getAllAlbums(): Promise<Result<Album[], CommandError>> {
+    try {
+      return { status: "ok", data: await TAURI_INVOKE("get_all_albums") };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
   },
-  async getArtistById(id: number): Promise<Artist> {
-    return await TAURI_INVOKE("get_artist_by_id", { id });
+  async getArtistById(id: number): Promise<Result<Artist, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("get_artist_by_id", { id }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
   },
-  async getAlbumTracks(albumId: number): Promise<Track[]> {
-    return await TAURI_INVOKE("get_album_tracks", { albumId });
+  async getAlbumTracks(
+    albumId: number,
+  ): Promise<Result<Track[], CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("get_album_tracks", { albumId }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
   },
-  async getAllArtists(): Promise<Artist[]> {
-    return await TAURI_INVOKE("get_all_artists");
+  async getAllArtists(): Promise<Result<Artist[], CommandError>> {
+    try {
+      return { status: "ok", data: await TAURI_INVOKE("get_all_artists") };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
   },
-  async getArtistAlbums(id: number): Promise<Album[]> {
-    return await TAURI_INVOKE("get_artist_albums", { id });
+  async getArtistAlbums(id: number): Promise<Result<Album[], CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("get_artist_albums", { id }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
   },
-  async getPlaylistTracks(playlistId: number): Promise<FullTrack[]> {
-    return await TAURI_INVOKE("get_playlist_tracks", { playlistId });
+  async getPlaylistTracks(
+    playlistId: number,
+  ): Promise<Result<FullTrack[], CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("get_playlist_tracks", { playlistId }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
   },
-  async getAllPlaylists(): Promise<Playlist[]> {
-    return await TAURI_INVOKE("get_all_playlists");
+  async getAllPlaylists(): Promise<Result<Playlist[], CommandError>> {
+    try {
+      return { status: "ok", data: await TAURI_INVOKE("get_all_playlists") };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
   },
-  async getPlaylist(id: number): Promise<Playlist | null> {
-    return await TAURI_INVOKE("get_playlist", { id });
+  async getPlaylist(id: number): Promise<Result<Playlist, CommandError>> {
+    try {
+      return { status: "ok", data: await TAURI_INVOKE("get_playlist", { id }) };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
   },
-  async search(term: string): Promise<SearchResults> {
-    return await TAURI_INVOKE("search", { term });
+  async search(term: string): Promise<Result<SearchResults, CommandError>> {
+    try {
+      return { status: "ok", data: await TAURI_INVOKE("search", { term }) };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
   },
-  async createPlaylist(name: string): Promise<void> {
-    await TAURI_INVOKE("create_playlist", { name });
+  async createPlaylist(name: string): Promise<Result<null, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("create_playlist", { name }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
   },
-  async deletePlaylist(id: number): Promise<void> {
-    await TAURI_INVOKE("delete_playlist", { id });
+  async deletePlaylist(id: number): Promise<Result<null, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("delete_playlist", { id }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
   },
   async addToPlaylist(
     trackId: number | null,
     playlistId: number | null,
     targetPlaylistId: number,
-  ): Promise<void> {
-    await TAURI_INVOKE("add_to_playlist", {
-      trackId,
-      playlistId,
-      targetPlaylistId,
-    });
+  ): Promise<Result<null, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("add_to_playlist", {
+          trackId,
+          playlistId,
+          targetPlaylistId,
+        }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
   },
   async removeFromPlaylist(
     trackId: number,
     position: number,
     playlistId: number,
-  ): Promise<void> {
-    await TAURI_INVOKE("remove_from_playlist", {
-      trackId,
-      position,
-      playlistId,
-    });
+  ): Promise<Result<null, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("remove_from_playlist", {
+          trackId,
+          position,
+          playlistId,
+        }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
   },
-  async getPlIdByName(name: string): Promise<number | null> {
-    return await TAURI_INVOKE("get_pl_id_by_name", { name });
+  async getPlIdByName(
+    name: string,
+  ): Promise<Result<number | null, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("get_pl_id_by_name", { name }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
   },
-  async getRecentlyAdded(): Promise<Album[]> {
-    return await TAURI_INVOKE("get_recently_added");
+  async getRecentlyAdded(): Promise<Result<Album[], CommandError>> {
+    try {
+      return { status: "ok", data: await TAURI_INVOKE("get_recently_added") };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
   },
-  async getDirectories(): Promise<string[]> {
-    return await TAURI_INVOKE("get_directories");
+  async getMostPlayed(
+    limit: number,
+  ): Promise<Result<FullTrack[], CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("get_most_played", { limit }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
   },
-  async addDirectory(path: string): Promise<void> {
-    await TAURI_INVOKE("add_directory", { path });
+  async getRecentlyPlayed(
+    limit: number,
+  ): Promise<Result<FullTrack[], CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("get_recently_played", { limit }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async getNeverPlayed(): Promise<Result<FullTrack[], CommandError>> {
+    try {
+      return { status: "ok", data: await TAURI_INVOKE("get_never_played") };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async getUnplayableTracks(): Promise<Result<FullTrack[], CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("get_unplayable_tracks"),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async clearPlaybackError(
+    trackId: bigint,
+  ): Promise<Result<null, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("clear_playback_error", { trackId }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
   },
-  async removeDirectory(path: string): Promise<void> {
-    await TAURI_INVOKE("remove_directory", { path });
+  async getDirectories(): Promise<Result<string[], CommandError>> {
+    try {
+      return { status: "ok", data: await TAURI_INVOKE("get_directories") };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
   },
-  async getPlayer(): Promise<Result<AudioPlayer, string>> {
+  async addDirectory(path: string): Promise<Result<null, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("add_directory", { path }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async removeDirectory(path: string): Promise<Result<null, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("remove_directory", { path }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async getPlayer(): Promise<Result<AudioPlayer, CommandError>> {
     try {
       return { status: "ok", data: await TAURI_INVOKE("get_player") };
     } catch (e) {
@@ -111,7 +308,7 @@ export const commands = {
       else return { status: "error", error: e as any };
     }
   },
-  async play(): Promise<Result<AudioPlayer, string>> {
+  async play(): Promise<Result<AudioPlayer, CommandError>> {
     try {
       return { status: "ok", data: await TAURI_INVOKE("play") };
     } catch (e) {
@@ -119,7 +316,7 @@ export const commands = {
       else return { status: "error", error: e as any };
     }
   },
-  async load(track: FullTrack): Promise<Result<AudioPlayer, string>> {
+  async load(track: FullTrack): Promise<Result<AudioPlayer, CommandError>> {
     try {
       return { status: "ok", data: await TAURI_INVOKE("load", { track }) };
     } catch (e) {
@@ -128,20 +325,20 @@ export const commands = {
     }
   },
   async changeQueueIndex(
-    oldIdx: number,
+    entryId: number,
     newIdx: number,
-  ): Promise<Result<AudioPlayer, string>> {
+  ): Promise<Result<AudioPlayer, CommandError>> {
     try {
       return {
         status: "ok",
-        data: await TAURI_INVOKE("change_queue_index", { oldIdx, newIdx }),
+        data: await TAURI_INVOKE("change_queue_index", { entryId, newIdx }),
       };
     } catch (e) {
       if (e instanceof Error) throw e;
       else return { status: "error", error: e as any };
     }
   },
-  async next(): Promise<Result<AudioPlayer, string>> {
+  async next(): Promise<Result<AudioPlayer, CommandError>> {
     try {
       return { status: "ok", data: await TAURI_INVOKE("next") };
     } catch (e) {
@@ -149,7 +346,7 @@ export const commands = {
       else return { status: "error", error: e as any };
     }
   },
-  async playNext(track: FullTrack): Promise<Result<AudioPlayer, string>> {
+  async playNext(track: FullTrack): Promise<Result<AudioPlayer, CommandError>> {
     try {
       return { status: "ok", data: await TAURI_INVOKE("play_next", { track }) };
     } catch (e) {
@@ -160,7 +357,7 @@ export const commands = {
   async playList(
     list: FullTrack[],
     idx: number,
-  ): Promise<Result<AudioPlayer, string>> {
+  ): Promise<Result<AudioPlayer, CommandError>> {
     try {
       return {
         status: "ok",
@@ -171,7 +368,69 @@ export const commands = {
       else return { status: "error", error: e as any };
     }
   },
-  async addToQueue(track: FullTrack): Promise<Result<AudioPlayer, string>> {
+  async playAlbum(
+    id: bigint,
+    startIndex: number | null,
+    shuffle: boolean,
+  ): Promise<Result<AudioPlayer, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("play_album", { id, startIndex, shuffle }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async playArtist(
+    id: bigint,
+    startIndex: number | null,
+    shuffle: boolean,
+  ): Promise<Result<AudioPlayer, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("play_artist", { id, startIndex, shuffle }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async playPlaylist(
+    id: bigint,
+    startIndex: number | null,
+    shuffle: boolean,
+  ): Promise<Result<AudioPlayer, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("play_playlist", { id, startIndex, shuffle }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async playSearch(
+    query: string,
+    startIndex: number | null,
+    shuffle: boolean,
+  ): Promise<Result<AudioPlayer, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("play_search", { query, startIndex, shuffle }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async addToQueue(
+    track: FullTrack,
+  ): Promise<Result<AudioPlayer, CommandError>> {
     try {
       return {
         status: "ok",
@@ -182,7 +441,7 @@ export const commands = {
       else return { status: "error", error: e as any };
     }
   },
-  async clear(): Promise<Result<AudioPlayer, string>> {
+  async clear(): Promise<Result<AudioPlayer, CommandError>> {
     try {
       return { status: "ok", data: await TAURI_INVOKE("clear") };
     } catch (e) {
@@ -190,7 +449,7 @@ export const commands = {
       else return { status: "error", error: e as any };
     }
   },
-  async pause(): Promise<Result<AudioPlayer, string>> {
+  async pause(): Promise<Result<AudioPlayer, CommandError>> {
     try {
       return { status: "ok", data: await TAURI_INVOKE("pause") };
     } catch (e) {
@@ -198,13 +457,18 @@ export const commands = {
       else return { status: "error", error: e as any };
     }
   },
-  async seek(time: number): Promise<void> {
-    await TAURI_INVOKE("seek", { time });
+  async seek(time: number): Promise<Result<null, CommandError>> {
+    try {
+      return { status: "ok", data: await TAURI_INVOKE("seek", { time }) };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
   },
   async playTracks(
     tracks: Track[],
     index: number,
-  ): Promise<Result<AudioPlayer, string>> {
+  ): Promise<Result<AudioPlayer, CommandError>> {
     try {
       return {
         status: "ok",
@@ -218,7 +482,7 @@ export const commands = {
   async addListToQueue(
     fulltracks: FullTrack[] | null,
     tracks: Track[] | null,
-  ): Promise<Result<AudioPlayer, string>> {
+  ): Promise<Result<AudioPlayer, CommandError>> {
     try {
       return {
         status: "ok",
@@ -232,7 +496,7 @@ export const commands = {
   async playListNext(
     fulltracks: FullTrack[] | null,
     tracks: Track[] | null,
-  ): Promise<Result<AudioPlayer, string>> {
+  ): Promise<Result<AudioPlayer, CommandError>> {
     try {
       return {
         status: "ok",
@@ -243,7 +507,7 @@ export const commands = {
       else return { status: "error", error: e as any };
     }
   },
-  async shuffle(): Promise<Result<AudioPlayer, string>> {
+  async shuffle(): Promise<Result<AudioPlayer, CommandError>> {
     try {
       return { status: "ok", data: await TAURI_INVOKE("shuffle") };
     } catch (e) {
@@ -251,7 +515,20 @@ export const commands = {
       else return { status: "error", error: e as any };
     }
   },
-  async previous(): Promise<Result<AudioPlayer, string>> {
+  async setShuffleStrategy(
+    strategy: ShuffleStrategy,
+  ): Promise<Result<AudioPlayer, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("set_shuffle_strategy", { strategy }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async previous(): Promise<Result<AudioPlayer, CommandError>> {
     try {
       return { status: "ok", data: await TAURI_INVOKE("previous") };
     } catch (e) {
@@ -259,22 +536,380 @@ export const commands = {
       else return { status: "error", error: e as any };
     }
   },
-  async removeFromQueue(index: number): Promise<Result<AudioPlayer, string>> {
+  async removeFromQueue(
+    entryId: number,
+  ): Promise<Result<AudioPlayer, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("remove_from_queue", { entryId }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async skipToEntry(
+    entryId: number,
+  ): Promise<Result<AudioPlayer, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("skip_to_entry", { entryId }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async undoQueue(): Promise<Result<AudioPlayer, CommandError>> {
+    try {
+      return { status: "ok", data: await TAURI_INVOKE("undo_queue") };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async redoQueue(): Promise<Result<AudioPlayer, CommandError>> {
+    try {
+      return { status: "ok", data: await TAURI_INVOKE("redo_queue") };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async setLoopMode(
+    mode: LoopType,
+  ): Promise<Result<AudioPlayer, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("set_loop_mode", { mode }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async setAutoplay(
+    enabled: boolean,
+  ): Promise<Result<AudioPlayer, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("set_autoplay", { enabled }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async setAbLoop(
+    start: number,
+    end: number,
+  ): Promise<Result<AudioPlayer, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("set_ab_loop", { start, end }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async clearAbLoop(): Promise<Result<AudioPlayer, CommandError>> {
+    try {
+      return { status: "ok", data: await TAURI_INVOKE("clear_ab_loop") };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async setTrackLongForm(
+    trackId: bigint,
+    longForm: boolean,
+  ): Promise<Result<null, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("set_track_long_form", { trackId, longForm }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async setDirLongForm(
+    path: string,
+    longForm: boolean,
+  ): Promise<Result<null, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("set_dir_long_form", { path, longForm }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async getLongFormDirs(): Promise<Result<string[], CommandError>> {
+    try {
+      return { status: "ok", data: await TAURI_INVOKE("get_long_form_dirs") };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async addBookmark(
+    name: string | null,
+  ): Promise<Result<Bookmark, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("add_bookmark", { name }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async listBookmarks(
+    trackId: bigint,
+  ): Promise<Result<Bookmark[], CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("list_bookmarks", { trackId }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async deleteBookmark(
+    bookmarkId: bigint,
+  ): Promise<Result<null, CommandError>> {
     try {
       return {
         status: "ok",
-        data: await TAURI_INVOKE("remove_from_queue", { index }),
+        data: await TAURI_INVOKE("delete_bookmark", { bookmarkId }),
       };
     } catch (e) {
       if (e instanceof Error) throw e;
       else return { status: "error", error: e as any };
     }
   },
-  async fulltrackFromId(id: number): Promise<FullTrack | null> {
-    return await TAURI_INVOKE("fulltrack_from_id", { id });
+  async jumpToBookmark(
+    bookmarkId: bigint,
+  ): Promise<Result<null, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("jump_to_bookmark", { bookmarkId }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
   },
-  async getLyrics(track: FullTrack): Promise<Lyrics> {
-    return await TAURI_INVOKE("get_lyrics", { track });
+  async setSleepTimer(
+    minutes: number,
+  ): Promise<Result<AudioPlayer, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("set_sleep_timer", { minutes }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async stopAfterTracks(
+    tracks: number,
+  ): Promise<Result<AudioPlayer, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("stop_after_tracks", { tracks }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async cancelSleepTimer(): Promise<Result<AudioPlayer, CommandError>> {
+    try {
+      return { status: "ok", data: await TAURI_INVOKE("cancel_sleep_timer") };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async getSettings(): Promise<Result<Settings, CommandError>> {
+    try {
+      return { status: "ok", data: await TAURI_INVOKE("get_settings") };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async setLongFormMinLength(
+    seconds: number,
+  ): Promise<Result<Settings, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("set_long_form_min_length", { seconds }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async setProgressInterval(
+    milliseconds: number,
+  ): Promise<Result<Settings, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("set_progress_interval", { milliseconds }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async setRemoteControl(
+    enabled: boolean,
+    bind: string | null,
+    port: number | null,
+  ): Promise<Result<Settings, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("set_remote_control", { enabled, bind, port }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async regenerateRemoteToken(): Promise<Result<Settings, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("regenerate_remote_token"),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async getRemoteToken(): Promise<Result<string, CommandError>> {
+    try {
+      return { status: "ok", data: await TAURI_INVOKE("get_remote_token") };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async setLastfm(
+    enabled: boolean,
+    url: string | null,
+    apiKey: string | null,
+    apiSecret: string | null,
+  ): Promise<Result<Settings, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("set_lastfm", {
+          enabled,
+          url,
+          apiKey,
+          apiSecret,
+        }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async lastfmLogin(
+    username: string,
+    password: string,
+  ): Promise<Result<Settings, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("lastfm_login", { username, password }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async lastfmLogout(): Promise<Result<Settings, CommandError>> {
+    try {
+      return { status: "ok", data: await TAURI_INVOKE("lastfm_logout") };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async setListenbrainz(
+    enabled: boolean,
+    url: string | null,
+    token: string | null,
+  ): Promise<Result<Settings, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("set_listenbrainz", { enabled, url, token }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async getPendingScrobbles(): Promise<
+    Result<PendingScrobble[], CommandError>
+  > {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("get_pending_scrobbles"),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async fulltrackFromId(
+    id: number,
+  ): Promise<Result<FullTrack | null, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("fulltrack_from_id", { id }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
+  },
+  async getLyrics(track: FullTrack): Promise<Result<Lyrics, CommandError>> {
+    try {
+      return {
+        status: "ok",
+        data: await TAURI_INVOKE("get_lyrics", { track }),
+      };
+    } catch (e) {
+      if (e instanceof Error) throw e;
+      else return { status: "error", error: e as any };
+    }
   },
 };
 
@@ -284,6 +919,10 @@ export const commands = {
 
 /** user-defined types **/
 
+/**
+ * A region of the current track that keeps repeating, in seconds.
+ */
+export type AbLoop = { start: number; end: number };
 export type Album = {
   id: bigint | null;
   artist_id: bigint;
@@ -299,17 +938,48 @@ export type AudioPlayer = {
   shuffle: boolean;
   state: PlayerState;
   history: FullTrack[];
-  real_queue: FullTrack[];
-  queue: FullTrack[];
+  real_queue: QueueEntry[];
+  queue: QueueEntry[];
   position: number;
   looping: LoopType;
+  sleep_timer: SleepTimer | null;
+  shuffle_strategy: ShuffleStrategy;
+  autoplay: boolean;
+  ab_loop: AbLoop | null;
+  context: PlaybackContext | null;
+  context_tracks: bigint[];
+};
+/**
+ * A named point of interest inside a track.
+ */
+export type Bookmark = {
+  id: bigint;
+  track_id: bigint;
+  name: string;
+  position: number;
+  created_at: bigint;
 };
+/**
+ * What commands hand back to the frontend when they fail. Serialized as
+ * `{ kind, message }` so the UI can tell an empty library from a broken one.
+ */
+export type CommandError =
+  | { kind: "Database"; message: string }
+  | { kind: "Io"; message: string }
+  | { kind: "NotFound"; message: string }
+  | { kind: "Engine"; message: string }
+  | { kind: "InvalidIndex"; message: bigint }
+  | { kind: "InvalidArgument"; message: string }
+  | { kind: "Network"; message: string }
+  | { kind: "Poisoned" };
 export type FullTrack = {
   track: Track;
   artist_name: string;
   album_title: string;
   album_art: string | null;
   playlist_position: bigint | null;
+  genre: string | null;
+  year: bigint;
 };
 /**
  * One line of line-synced lyrics (line-level TTML or LRC)
@@ -332,6 +1002,31 @@ export type Lyrics = {
 };
 export type LyricsType = "Line" | "Syllable" | "Unsynced";
 export type MatchReason = "Title" | "Artist" | "Album" | "Lyrics";
+/**
+ * A finished listen waiting to be submitted. The track details are copied so
+ * it survives the track leaving the library.
+ */
+export type PendingScrobble = {
+  id: bigint;
+  service: ScrobbleService;
+  artist: string;
+  album: string;
+  title: string;
+  track_number: bigint;
+  duration: bigint;
+  timestamp: bigint;
+  mbid: string | null;
+  attempts: bigint;
+};
+/**
+ * Where the queue was built from. Lets the UI say what's playing and the
+ * queue follow its source when that changes.
+ */
+export type PlaybackContext =
+  | { Album: { id: bigint; name: string } }
+  | { Artist: { id: bigint; name: string } }
+  | { Playlist: { id: bigint; name: string } }
+  | { Search: { query: string } };
 export type PlayerState = "Paused" | "Playing" | "Empty" | "Stopped";
 export type Playlist = {
   id: bigint | null;
@@ -339,12 +1034,81 @@ export type Playlist = {
   cover_path: string | null;
   created_at: bigint;
 };
+/**
+ * A track's place in the queue. The same track can be queued more than
+ * once, `entry_id` tells the copies apart.
+ */
+export type QueueEntry = {
+  track: Track;
+  artist_name: string;
+  album_title: string;
+  album_art: string | null;
+  playlist_position: bigint | null;
+  genre: string | null;
+  year: bigint;
+} & { entry_id: number };
+export type RemoteSettings = {
+  enabled: boolean;
+  /**
+   * Only reachable from this machine unless changed
+   */
+  bind: string;
+  port: number;
+  /**
+   * Sent as `Authorization: Bearer <token>`, or `?token=` for WebSockets
+   */
+  token: string;
+};
+export type ScrobbleService = "LastFm" | "ListenBrainz";
+export type ScrobblerSettings = {
+  lastfm_enabled: boolean;
+  /**
+   * Any Audioscrobbler 2.0 endpoint, Libre.fm for example
+   */
+  lastfm_url: string;
+  lastfm_api_key: string;
+  lastfm_api_secret: string;
+  lastfm_session_key: string;
+  listenbrainz_enabled: boolean;
+  listenbrainz_url: string;
+  listenbrainz_token: string;
+};
 export type SearchResults = {
   tracks: TrackResult[];
   albums: Album[];
   artists: Artist[];
   playlists: Playlist[];
 };
+export type Settings = {
+  /**
+   * Tracks at least this many seconds long resume where they were left, 0 turns it off
+   */
+  long_form_min_length: number;
+  /**
+   * Milliseconds between `progress-changed` events while playing
+   */
+  progress_interval: number;
+  remote: RemoteSettings;
+  scrobbler: ScrobblerSettings;
+};
+export type ShuffleStrategy =
+  /**
+   * Every order equally likely
+   */
+  | "Uniform"
+  /**
+   * Tracks by the same artist and from the same album spread out evenly
+   */
+  | "Balanced";
+export type SleepTimer =
+  /**
+   * Pause once `remaining` seconds run out
+   */
+  | { Duration: { remaining: number } }
+  /**
+   * Stop once this many more tracks have finished, 0 being the current one
+   */
+  | { AfterTracks: { tracks: number } };
 /**
  * One line in word-timed lyrics (word-level TTML)
  */
@@ -379,6 +1143,18 @@ export type Track = {
   lyrics: string | null;
   composer: string | null;
   added_at: bigint | null;
+  track_gain: number | null;
+  track_peak: number | null;
+  album_gain: number | null;
+  album_peak: number | null;
+  play_count: bigint;
+  skip_count: bigint;
+  last_played: bigint | null;
+  long_form: boolean;
+  musicbrainz_recording_id: string | null;
+  start_offset: number | null;
+  end_offset: number | null;
+  playback_error: string | null;
 };
 export type TrackResult = { track: FullTrack; reasons: MatchReason[] };
 
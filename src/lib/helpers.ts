@@ -1,5 +1,5 @@
 import { commands } from "./bindings";
-import type { FullTrack } from "./bindings";
+import type { CommandError, FullTrack, Result } from "./bindings";
 
 export function areArraysEqual<T>(arr1: T[], arr2: T[]): boolean {
   if (arr1 === arr2) return true;
@@ -13,6 +13,14 @@ export function areArraysEqual<T>(arr1: T[], arr2: T[]): boolean {
   return true;
 }
 
+/** A command's data, or `fallback` once the failure has been logged. */
+export function unwrapOr<T>(result: Result<T, CommandError>, fallback: T): T {
+  if (result.status === "ok") return result.data;
+
+  console.error(result.error);
+  return fallback;
+}
+
 export function formatDuration(seconds: bigint | number): string {
   const s = Number(seconds);
   const m = Math.floor(s / 60);
@@ -1,6 +1,6 @@
 import { listen } from "@tauri-apps/api/event";
 import type { AudioPlayer } from "./bindings";
-import type { FullTrack } from "./bindings";
+import type { FullTrack, QueueEntry } from "./bindings";
 import { commands } from "./bindings";
 import { areArraysEqual } from "./helpers";
 
@@ -16,7 +16,7 @@ export type PlayerState = (typeof PlayerState)[keyof typeof PlayerState];
 class PlayerManager {
   currentlyPlaying = $state<FullTrack | null>(null);
   state = $state<PlayerState>(PlayerState.Empty);
-  queue = $state<FullTrack[]>([]);
+  queue = $state<QueueEntry[]>([]);
   history = $state<FullTrack[]>([]);
   position = $state<number>(0.0);
   shuffle = $state<boolean>(false);
@@ -60,7 +60,7 @@ class PlayerManager {
       }
     });
 
-    await listen<FullTrack[]>("queue-changed", (event) => {
+    await listen<QueueEntry[]>("queue-changed", (event) => {
       let newQ = event.payload;
 
       if (!areArraysEqual(this.queue ?? [], newQ ?? [])) {